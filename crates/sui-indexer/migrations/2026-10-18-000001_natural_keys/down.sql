@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+UPDATE check_point SET version = version - 1;
+
+DROP INDEX IF EXISTS orders_natural_key;
+ALTER TABLE orders DROP COLUMN IF EXISTS "tx_digest", DROP COLUMN IF EXISTS "event_seq";
+
+DROP INDEX IF EXISTS offers_natural_key;
+ALTER TABLE offers DROP COLUMN IF EXISTS "tx_digest", DROP COLUMN IF EXISTS "event_seq";
+
+DROP INDEX IF EXISTS lists_natural_key;
+ALTER TABLE lists DROP COLUMN IF EXISTS "tx_digest", DROP COLUMN IF EXISTS "event_seq";
+
+DROP INDEX IF EXISTS activities_natural_key;
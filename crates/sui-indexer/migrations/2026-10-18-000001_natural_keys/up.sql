@@ -0,0 +1,39 @@
+-- Rows are keyed by the chain data that produced them so replaying a
+-- checkpoint never duplicates them.
+
+-- activities: one row per object version and activity type.
+DELETE FROM activities a USING activities b
+WHERE a.id > b.id
+  AND a.chain_id = b.chain_id
+  AND a.collection_data_id_hash = b.collection_data_id_hash
+  AND a.token_data_id_hash = b.token_data_id_hash
+  AND a.version = b.version
+  AND a.transfer_type = b.transfer_type;
+
+CREATE UNIQUE INDEX activities_natural_key ON activities (
+    chain_id, collection_data_id_hash, token_data_id_hash, version, transfer_type
+);
+
+-- lists, offers and orders: one row per emitting event. Rows written before
+-- provenance was tracked keep a unique placeholder digest.
+ALTER TABLE lists
+    ADD COLUMN "tx_digest" varchar(255) NOT NULL DEFAULT '',
+    ADD COLUMN "event_seq" int8 NOT NULL DEFAULT 0;
+UPDATE lists SET tx_digest = 'legacy-' || id;
+CREATE UNIQUE INDEX lists_natural_key ON lists (chain_id, tx_digest, event_seq);
+
+ALTER TABLE offers
+    ADD COLUMN "tx_digest" varchar(255) NOT NULL DEFAULT '',
+    ADD COLUMN "event_seq" int8 NOT NULL DEFAULT 0;
+UPDATE offers SET tx_digest = 'legacy-' || id;
+CREATE UNIQUE INDEX offers_natural_key ON offers (chain_id, tx_digest, event_seq);
+
+ALTER TABLE orders
+    ADD COLUMN "tx_digest" varchar(255) NOT NULL DEFAULT '',
+    ADD COLUMN "event_seq" int8 NOT NULL DEFAULT 0;
+UPDATE orders SET tx_digest = 'legacy-' || id;
+CREATE UNIQUE INDEX orders_natural_key ON orders (chain_id, tx_digest, event_seq);
+
+-- check_point.version now holds the next checkpoint to index instead of the
+-- last one committed.
+UPDATE check_point SET version = version + 1;
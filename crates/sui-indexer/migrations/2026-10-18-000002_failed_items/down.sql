@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS failed_items;
+DROP TYPE IF EXISTS failed_item_kind;
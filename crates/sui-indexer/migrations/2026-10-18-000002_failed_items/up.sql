@@ -0,0 +1,29 @@
+-- Events and objects that failed to parse, kept for `retry-failed`.
+DO
+$$
+BEGIN
+CREATE TYPE failed_item_kind AS ENUM ('event', 'object');
+END
+$$;
+
+CREATE TABLE failed_items (
+   "id" BIGSERIAL PRIMARY KEY,
+   "chain_id" int8 NOT NULL,
+   "checkpoint" int8 NOT NULL,
+   "tx_digest" varchar(255) NOT NULL,
+   "item_kind" failed_item_kind NOT NULL,
+   -- event sequence number or object id.
+   "item_key" varchar(255) NOT NULL,
+   "raw_json" text NOT NULL,
+   "error" text NOT NULL,
+   "attempts" int4 NOT NULL DEFAULT 1,
+   "resolved" bool NOT NULL DEFAULT false,
+   "created_at" timestamp NOT NULL DEFAULT now(),
+   "updated_at" timestamp NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX failed_items_natural_key ON failed_items (
+    chain_id, item_kind, tx_digest, item_key
+);
+CREATE INDEX failed_items_unresolved ON failed_items (chain_id, checkpoint)
+    WHERE NOT resolved;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+UPDATE offers SET expire_time = offer_time WHERE expire_time IS NULL;
+ALTER TABLE offers ALTER COLUMN expire_time SET NOT NULL;
@@ -0,0 +1,2 @@
+-- Origin Byte bids never expire.
+ALTER TABLE offers ALTER COLUMN expire_time DROP NOT NULL;
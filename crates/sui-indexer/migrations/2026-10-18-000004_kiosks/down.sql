@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+UPDATE lists SET list_id = split_part(list_id, ':', 1) WHERE market_type = 'kiosk';
+
+DROP TABLE IF EXISTS kiosks;
@@ -0,0 +1,14 @@
+-- Kiosk owners, tracked from the `KioskOwnerCap` objects.
+CREATE TABLE kiosks (
+   "chain_id" int8 NOT NULL,
+   "kiosk_id" varchar(255) NOT NULL,
+   "owner_cap_id" varchar(255) NOT NULL,
+   "owner_address" varchar(255) NOT NULL,
+   "version" int8 NOT NULL,
+   "created_at" int8 NOT NULL,
+   "updated_at" int8 NOT NULL,
+   PRIMARY KEY (chain_id, kiosk_id)
+);
+
+-- Kiosk listings are keyed by kiosk and item.
+UPDATE lists SET list_id = list_id || ':' || token_id WHERE market_type = 'kiosk';
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS transfer_policy_rules;
+DROP TABLE IF EXISTS transfer_policies;
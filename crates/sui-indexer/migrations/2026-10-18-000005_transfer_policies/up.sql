@@ -0,0 +1,32 @@
+-- One `TransferPolicy<T>` per NFT type, with its royalty, floor price and
+-- kiosk lock rules summarised from `transfer_policy_rules`.
+CREATE TABLE transfer_policies (
+   "chain_id" int8 NOT NULL,
+   "nft_type" text NOT NULL,
+   "policy_id" varchar(255) NOT NULL,
+   -- JSON array of the rule types attached to the policy.
+   "rules" text NOT NULL,
+   "royalty_bp" int8,
+   "royalty_min_amount" int8,
+   "floor_price" int8,
+   "kiosk_lock" bool NOT NULL DEFAULT false,
+   -- holder of the `TransferPolicyCap`, who collects the royalties.
+   "payee_address" varchar(255),
+   "version" int8 NOT NULL,
+   "created_at" int8 NOT NULL,
+   "updated_at" int8 NOT NULL,
+   PRIMARY KEY (chain_id, nft_type)
+);
+
+CREATE INDEX transfer_policies_policy_id ON transfer_policies (chain_id, policy_id);
+
+-- Rule configs, stored by the policy as dynamic fields.
+CREATE TABLE transfer_policy_rules (
+   "chain_id" int8 NOT NULL,
+   "policy_id" varchar(255) NOT NULL,
+   "rule_type" text NOT NULL,
+   "config" text NOT NULL,
+   "version" int8 NOT NULL,
+   "updated_at" int8 NOT NULL,
+   PRIMARY KEY (chain_id, policy_id, rule_type)
+);
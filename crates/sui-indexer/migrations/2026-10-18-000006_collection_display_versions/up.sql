@@ -0,0 +1,13 @@
+-- Every version of a collection's `Display<T>` object, newest last.
+CREATE TABLE collection_display_versions (
+   "chain_id" int8 NOT NULL,
+   "collection_id" varchar(255) NOT NULL,
+   -- object version, bumped by every edit.
+   "version" int8 NOT NULL,
+   -- `Display.version`, bumped by `display::update_version`.
+   "display_version" int8 NOT NULL,
+   "metadata" text NOT NULL,
+   "tx" varchar(255),
+   "created_at" int8 NOT NULL,
+   PRIMARY KEY (chain_id, collection_id, version)
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS marketplace_packages;
+DROP TYPE IF EXISTS marketplace;
@@ -0,0 +1,19 @@
+-- Marketplace package versions found by following their `UpgradeCap`,
+-- on top of the ones configured.
+DO
+$$
+BEGIN
+CREATE TYPE marketplace AS ENUM ('bob_yard', 'origin_byte', 'origin_byte_orderbook');
+END
+$$;
+
+CREATE TABLE marketplace_packages (
+   "chain_id" int8 NOT NULL,
+   "package_id" varchar(255) NOT NULL,
+   "marketplace" marketplace NOT NULL,
+   "upgrade_cap_id" varchar(255),
+   -- `UpgradeCap.version` the package was published with.
+   "version" int8 NOT NULL,
+   "created_at" int8 NOT NULL,
+   PRIMARY KEY (chain_id, package_id)
+);
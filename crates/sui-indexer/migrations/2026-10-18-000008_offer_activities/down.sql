@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+-- Postgres cannot drop enum values, `offer_made` and `offer_canceled` stay.
+DROP INDEX IF EXISTS offers_best_collection;
+DROP INDEX IF EXISTS offers_best_token;
+
+ALTER TABLE offers
+    DROP COLUMN IF EXISTS "collection_type",
+    DROP COLUMN IF EXISTS "token_id";
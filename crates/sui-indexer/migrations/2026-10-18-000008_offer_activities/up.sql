@@ -0,0 +1,20 @@
+ALTER TYPE activity_type ADD VALUE IF NOT EXISTS 'offer_made';
+ALTER TYPE activity_type ADD VALUE IF NOT EXISTS 'offer_canceled';
+
+-- Token offers name their token, collection-wide bids only the NFT type.
+ALTER TABLE offers
+    ADD COLUMN "token_id" varchar(255),
+    ADD COLUMN "collection_type" varchar(255);
+
+UPDATE offers o SET token_id = l.token_id
+FROM lists l
+WHERE l.chain_id = o.chain_id AND l.list_id = o.list_id;
+
+UPDATE offers o SET collection_type = t.collection_type
+FROM tokens t
+WHERE t.chain_id = o.chain_id AND t.token_id = o.token_id;
+
+CREATE INDEX offers_best_token ON offers (chain_id, token_id, offer_value DESC)
+    WHERE offer_type = 'listed';
+CREATE INDEX offers_best_collection ON offers (chain_id, collection_type, offer_value DESC)
+    WHERE offer_type = 'listed';
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+-- Postgres cannot drop enum values, `expired` and `offer_expired` stay.
+DROP INDEX IF EXISTS offers_open_expiry;
+DROP INDEX IF EXISTS lists_open_expiry;
@@ -0,0 +1,8 @@
+ALTER TYPE activity_type ADD VALUE IF NOT EXISTS 'expired';
+ALTER TYPE activity_type ADD VALUE IF NOT EXISTS 'offer_expired';
+
+-- Open rows the expiry sweeper looks at.
+CREATE INDEX lists_open_expiry ON lists (chain_id, expire_time)
+    WHERE list_type = 'listed' AND expire_time IS NOT NULL;
+CREATE INDEX offers_open_expiry ON offers (chain_id, expire_time)
+    WHERE offer_type = 'listed' AND expire_time IS NOT NULL;
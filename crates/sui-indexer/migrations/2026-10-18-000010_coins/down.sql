@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE activities ALTER COLUMN coin_amount TYPE int8;
+ALTER TABLE activities ALTER COLUMN token_amount TYPE int8;
+ALTER TABLE orders ALTER COLUMN value TYPE int8;
+ALTER TABLE offers ALTER COLUMN offer_value TYPE int8;
+ALTER TABLE lists ALTER COLUMN seller_value TYPE int8;
+DROP TABLE IF EXISTS coins;
@@ -0,0 +1,25 @@
+-- Coin types are package qualified, one registry serves every network.
+CREATE TABLE coins (
+    "id" SERIAL PRIMARY KEY,
+    "coin_type" text NOT NULL UNIQUE,
+    "symbol" varchar(255),
+    "name" varchar(255),
+    "decimals" int4,
+    "icon_url" text,
+    "metadata_id" varchar(255),
+    "version" int8 NOT NULL DEFAULT 0,
+    "created_at" int8 NOT NULL,
+    "updated_at" int8 NOT NULL
+);
+
+-- `coin_id` 1 has always meant SUI.
+INSERT INTO coins (id, coin_type, symbol, name, decimals, created_at, updated_at)
+VALUES (1, '0x2::sui::SUI', 'SUI', 'Sui', 9, 0, 0);
+SELECT setval(pg_get_serial_sequence('coins', 'id'), 1);
+
+-- Move u64 amounts do not fit in int8.
+ALTER TABLE lists ALTER COLUMN seller_value TYPE numeric(20, 0);
+ALTER TABLE offers ALTER COLUMN offer_value TYPE numeric(20, 0);
+ALTER TABLE orders ALTER COLUMN value TYPE numeric(20, 0);
+ALTER TABLE activities ALTER COLUMN token_amount TYPE numeric(20, 0);
+ALTER TABLE activities ALTER COLUMN coin_amount TYPE numeric(20, 0);
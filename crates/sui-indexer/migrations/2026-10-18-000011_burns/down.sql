@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+-- Postgres cannot drop enum values, `burned` stays.
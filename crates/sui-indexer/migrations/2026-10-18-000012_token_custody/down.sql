@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+-- Postgres cannot drop enum values, `wrapped` and `unwrapped` stay.
+DROP TABLE IF EXISTS token_custody;
@@ -0,0 +1,24 @@
+-- Wrapper object or protocol holding each wrapped token, the row stays once
+-- the token is unwrapped with `unwrapped_at` set.
+CREATE TABLE token_custody (
+   "chain_id" int8 NOT NULL,
+   "token_id" varchar(255) NOT NULL,
+   "custodian_id" varchar(255),
+   "custodian_type" text,
+   "protocol" text,
+   "owner_address" varchar(255),
+   "version" int8 NOT NULL,
+   "tx_digest" varchar(255) NOT NULL,
+   "wrapped_at" int8 NOT NULL,
+   "unwrapped_at" int8,
+   "created_at" int8 NOT NULL,
+   "updated_at" int8 NOT NULL,
+   PRIMARY KEY (chain_id, token_id)
+);
+
+CREATE INDEX token_custody_open_owner ON token_custody (chain_id, owner_address)
+   WHERE unwrapped_at IS NULL;
+CREATE INDEX token_custody_custodian ON token_custody (chain_id, custodian_id);
+
+ALTER TYPE activity_type ADD VALUE IF NOT EXISTS 'wrapped';
+ALTER TYPE activity_type ADD VALUE IF NOT EXISTS 'unwrapped';
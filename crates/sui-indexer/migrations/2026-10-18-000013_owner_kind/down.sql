@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tokens DROP COLUMN IF EXISTS owner_kind;
+DROP TYPE IF EXISTS owner_kind;
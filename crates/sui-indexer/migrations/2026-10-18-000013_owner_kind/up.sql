@@ -0,0 +1,3 @@
+-- What holds each token, `owner_address` being the address it resolves to.
+CREATE TYPE owner_kind AS ENUM ('address', 'kiosk', 'object', 'shared', 'immutable');
+ALTER TABLE tokens ADD COLUMN "owner_kind" owner_kind;
@@ -0,0 +1,22 @@
+-- One row per owner of a token, open while `to_version` is unset. Tokens
+-- get their first row the next time they change.
+CREATE TABLE token_ownership_history (
+   "id" BIGSERIAL PRIMARY KEY,
+   "chain_id" int8 NOT NULL,
+   "token_id" varchar(255) NOT NULL,
+   "owner_address" varchar(255),
+   "owner_kind" owner_kind,
+   "from_version" int8 NOT NULL,
+   "from_checkpoint" int8 NOT NULL,
+   "from_time" int8 NOT NULL,
+   "to_version" int8,
+   "to_checkpoint" int8,
+   "to_time" int8,
+   "tx_digest" varchar(255),
+   UNIQUE (chain_id, token_id, from_version)
+);
+
+CREATE INDEX token_ownership_history_token
+   ON token_ownership_history (chain_id, token_id, from_checkpoint);
+CREATE INDEX token_ownership_history_owner
+   ON token_ownership_history (chain_id, owner_address, from_time);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS tokens_collection_id;
+ALTER TABLE collections DROP COLUMN IF EXISTS holders;
+ALTER TABLE collections DROP COLUMN IF EXISTS burned;
+ALTER TABLE collections DROP COLUMN IF EXISTS minted;
@@ -0,0 +1,22 @@
+-- Token counters kept by the indexer, `supply` being what is still in
+-- circulation: minted - burned.
+ALTER TABLE collections ADD COLUMN "minted" int8 NOT NULL DEFAULT 0;
+ALTER TABLE collections ADD COLUMN "burned" int8 NOT NULL DEFAULT 0;
+ALTER TABLE collections ADD COLUMN "holders" int8 NOT NULL DEFAULT 0;
+
+CREATE INDEX tokens_collection_id ON tokens (collection_id);
+
+UPDATE collections c SET
+   minted = t.minted,
+   burned = t.burned,
+   supply = t.minted - t.burned,
+   holders = t.holders
+FROM (
+   SELECT collection_id,
+      count(*) AS minted,
+      count(*) FILTER (WHERE status = 'delete') AS burned,
+      count(DISTINCT owner_address) FILTER (WHERE status IS DISTINCT FROM 'delete') AS holders
+   FROM tokens
+   GROUP BY collection_id
+) t
+WHERE c.collection_id = t.collection_id;
@@ -0,0 +1,14 @@
+-- Traits parsed from `tokens.attributes`, one row per value. Tokens get
+-- theirs the next time they change.
+CREATE TABLE token_attributes (
+   "chain_id" int8 NOT NULL,
+   "token_id" varchar(255) NOT NULL,
+   "collection_id" varchar(255) NOT NULL,
+   "trait_type" varchar(255) NOT NULL,
+   "value" text NOT NULL,
+   "version" int8 NOT NULL,
+   PRIMARY KEY (chain_id, token_id, trait_type, value)
+);
+
+CREATE INDEX token_attributes_trait
+   ON token_attributes (chain_id, collection_id, trait_type, value);
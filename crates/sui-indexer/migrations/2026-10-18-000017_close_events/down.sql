@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS offers_closed_by;
+DROP INDEX IF EXISTS lists_closed_by;
+ALTER TABLE offers DROP COLUMN IF EXISTS closed_event_seq;
+ALTER TABLE offers DROP COLUMN IF EXISTS closed_tx_digest;
+ALTER TABLE lists DROP COLUMN IF EXISTS closed_event_seq;
+ALTER TABLE lists DROP COLUMN IF EXISTS closed_tx_digest;
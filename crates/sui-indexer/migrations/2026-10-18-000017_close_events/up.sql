@@ -0,0 +1,10 @@
+-- Event that closed a listing or offer, so replaying it closes nothing else.
+ALTER TABLE lists ADD COLUMN "closed_tx_digest" varchar(255);
+ALTER TABLE lists ADD COLUMN "closed_event_seq" int8;
+ALTER TABLE offers ADD COLUMN "closed_tx_digest" varchar(255);
+ALTER TABLE offers ADD COLUMN "closed_event_seq" int8;
+
+CREATE INDEX lists_closed_by ON lists (chain_id, closed_tx_digest, closed_event_seq)
+   WHERE closed_tx_digest IS NOT NULL;
+CREATE INDEX offers_closed_by ON offers (chain_id, closed_tx_digest, closed_event_seq)
+   WHERE closed_tx_digest IS NOT NULL;
@@ -0,0 +1,19 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS activities_natural_key;
+
+DELETE FROM activities a USING activities b
+WHERE a.id > b.id
+  AND a.chain_id = b.chain_id
+  AND a.collection_data_id_hash = b.collection_data_id_hash
+  AND a.token_data_id_hash = b.token_data_id_hash
+  AND a.version = b.version
+  AND a.transfer_type = b.transfer_type;
+
+CREATE UNIQUE INDEX activities_natural_key ON activities (
+    chain_id, collection_data_id_hash, token_data_id_hash, version, transfer_type
+);
+
+ALTER TABLE activities
+    ALTER COLUMN "tx" DROP NOT NULL,
+    ALTER COLUMN "tx" DROP DEFAULT;
+UPDATE activities SET tx = NULL WHERE tx = '';
@@ -0,0 +1,13 @@
+-- Activities produced by events are keyed by the event as well: several
+-- offers or expiries may share a checkpoint, collection and type. `tx` is
+-- part of the key, an empty one never equals NULL so it is made required.
+UPDATE activities SET tx = '' WHERE tx IS NULL;
+ALTER TABLE activities
+    ALTER COLUMN "tx" SET DEFAULT '',
+    ALTER COLUMN "tx" SET NOT NULL;
+
+DROP INDEX IF EXISTS activities_natural_key;
+CREATE UNIQUE INDEX activities_natural_key ON activities (
+    chain_id, collection_data_id_hash, token_data_id_hash, version, transfer_type,
+    tx, event_sequence_number
+);
@@ -0,0 +1,10 @@
+-- Origin Byte orderbooks and the fungible token they trade in, their ask,
+-- bid and trade events only name the book.
+CREATE TABLE orderbooks (
+   "chain_id" int8 NOT NULL,
+   "orderbook_id" varchar(255) NOT NULL,
+   "nft_type" text NOT NULL,
+   "coin_id" int4 NOT NULL,
+   "created_at" int8 NOT NULL,
+   PRIMARY KEY (chain_id, orderbook_id)
+);
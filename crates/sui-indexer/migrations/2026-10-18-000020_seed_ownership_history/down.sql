@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+-- Seeded rows cannot be told apart from recorded ones, they are kept.
+SELECT 1;
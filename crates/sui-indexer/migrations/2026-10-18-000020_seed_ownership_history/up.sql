@@ -0,0 +1,20 @@
+-- One open row per token indexed before the history was kept, holding its
+-- current owner since its last change. The checkpoint of that change is
+-- unknown, the rows start at the checkpoint indexing resumes from so
+-- checkpoint queries never place them earlier.
+INSERT INTO token_ownership_history (
+    chain_id, token_id, owner_address, owner_kind, from_version,
+    from_checkpoint, from_time, tx_digest
+)
+SELECT t.chain_id, t.token_id, t.owner_address, t.owner_kind, t.version,
+       COALESCE(c.version, 0), t.updated_at, t.tx
+FROM tokens t
+LEFT JOIN check_point c ON c.chain_id = t.chain_id
+WHERE t.status IS DISTINCT FROM 'delete'
+  AND NOT EXISTS (
+    SELECT 1 FROM token_ownership_history h
+    WHERE h.chain_id = t.chain_id
+      AND h.token_id = t.token_id
+      AND h.to_version IS NULL
+  )
+ON CONFLICT (chain_id, token_id, from_version) DO NOTHING;
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE token_attributes ALTER COLUMN "value" TYPE text;
@@ -0,0 +1,5 @@
+-- Values are part of the primary key and of `token_attributes_trait`, an
+-- unbounded one can outgrow a btree entry. The parser keeps values of up to
+-- 255 bytes, longer ones are descriptions rather than traits.
+DELETE FROM token_attributes WHERE octet_length(value) > 255;
+ALTER TABLE token_attributes ALTER COLUMN "value" TYPE varchar(255);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE collections DROP CONSTRAINT collections_pkey;
+ALTER TABLE collections ADD PRIMARY KEY (collection_id);
+
+ALTER TABLE tokens DROP CONSTRAINT tokens_pkey;
+ALTER TABLE tokens ADD PRIMARY KEY (token_id);
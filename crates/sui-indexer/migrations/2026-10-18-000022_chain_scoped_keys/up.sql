@@ -0,0 +1,7 @@
+-- Object ids are only unique within a chain, key tokens and collections by
+-- chain so indexing a second network can't overwrite the first one's rows.
+ALTER TABLE tokens DROP CONSTRAINT tokens_pkey;
+ALTER TABLE tokens ADD PRIMARY KEY (chain_id, token_id);
+
+ALTER TABLE collections DROP CONSTRAINT collections_pkey;
+ALTER TABLE collections ADD PRIMARY KEY (chain_id, collection_id);
@@ -0,0 +1,191 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::indexer::CheckpointData;
+
+const INDEX_FILE: &str = "index.jsonl";
+const FILES_PER_DIR: u64 = 10_000;
+const ZSTD_LEVEL: i32 = 3;
+
+/// One line of the archive index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub sequence_number: u64,
+    pub checkpoint_digest: String,
+    /// Path relative to the archive directory.
+    pub file: String,
+    pub size: u64,
+    /// blake3 of the compressed file.
+    pub blake3: String,
+}
+
+/// Downloaded checkpoints stored as zstd compressed JSON, one file per
+/// checkpoint, with an append-only index. A sequence number written twice
+/// keeps its last index entry.
+pub struct CheckpointArchive {
+    dir: PathBuf,
+    /// Unset when opened read-only.
+    index: Option<File>,
+}
+
+impl CheckpointArchive {
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(INDEX_FILE))?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            index: Some(index),
+        })
+    }
+
+    /// Open an existing archive for reading only, nothing is created or
+    /// appended to.
+    pub fn open_read(dir: &Path) -> Result<Self> {
+        if !dir.join(INDEX_FILE).is_file() {
+            bail!("{} is not a checkpoint archive", dir.display());
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            index: None,
+        })
+    }
+
+    pub fn write(&mut self, data: &CheckpointData) -> Result<ArchiveEntry> {
+        let index = match self.index.as_mut() {
+            Some(index) => index,
+            None => bail!("Archive {} is opened read-only", self.dir.display()),
+        };
+        let sequence_number = data.0.sequence_number;
+        let file = relative_path(sequence_number);
+        let path = self.dir.join(&file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let json = serde_json::to_vec(data)?;
+        let compressed = zstd::encode_all(&json[..], ZSTD_LEVEL)?;
+
+        // write then rename so readers never see a partial file.
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, &compressed)?;
+        fs::rename(&tmp, &path)?;
+
+        let entry = ArchiveEntry {
+            sequence_number,
+            checkpoint_digest: data.0.digest.to_string(),
+            file,
+            size: compressed.len() as u64,
+            blake3: blake3::hash(&compressed).to_hex().to_string(),
+        };
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        index.write_all(&line)?;
+        index.flush()?;
+
+        Ok(entry)
+    }
+
+    pub fn read(&self, entry: &ArchiveEntry) -> Result<CheckpointData> {
+        let compressed = fs::read(self.dir.join(&entry.file))?;
+        let json = zstd::decode_all(&compressed[..])?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    /// Size of the index file, which only grows as checkpoints are added.
+    pub fn index_len(&self) -> Result<u64> {
+        Ok(fs::metadata(self.dir.join(INDEX_FILE))?.len())
+    }
+
+    pub fn entries(&self) -> Result<BTreeMap<u64, ArchiveEntry>> {
+        let index = File::open(self.dir.join(INDEX_FILE))?;
+        let mut entries = BTreeMap::new();
+        for line in BufReader::new(index).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: ArchiveEntry = serde_json::from_str(&line)?;
+            entries.insert(entry.sequence_number, entry);
+        }
+        Ok(entries)
+    }
+
+    /// Check that every checkpoint in `from..=to` is archived, that its
+    /// file matches the indexed size and hash, and that it decodes to the
+    /// indexed checkpoint.
+    pub fn verify(&self, from: u64, to: u64) -> Result<()> {
+        let entries = self.entries()?;
+        let mut failures = vec![];
+
+        for seq in from..=to {
+            let entry = match entries.get(&seq) {
+                Some(entry) => entry,
+                None => {
+                    failures.push(format!("{}: missing", seq));
+                    continue;
+                }
+            };
+
+            let compressed = match fs::read(self.dir.join(&entry.file)) {
+                Ok(compressed) => compressed,
+                Err(e) => {
+                    failures.push(format!("{}: {}", seq, e));
+                    continue;
+                }
+            };
+            if compressed.len() as u64 != entry.size
+                || blake3::hash(&compressed).to_hex().to_string()
+                    != entry.blake3
+            {
+                failures.push(format!("{}: checksum mismatch", seq));
+                continue;
+            }
+
+            match self.read(entry) {
+                Ok(data) => {
+                    if data.0.digest.to_string() != entry.checkpoint_digest {
+                        failures.push(format!("{}: digest mismatch", seq));
+                    }
+                }
+                Err(e) => failures.push(format!("{}: {}", seq, e)),
+            }
+        }
+
+        if !failures.is_empty() {
+            bail!(
+                "Archive verification failed for {} checkpoints: {}",
+                failures.len(),
+                failures.join(", ")
+            );
+        }
+
+        info!(from, to, "Archive verified");
+        Ok(())
+    }
+}
+
+fn relative_path(sequence_number: u64) -> String {
+    format!(
+        "{:08}/{:012}.json.zst",
+        sequence_number / FILES_PER_DIR,
+        sequence_number
+    )
+}
+
+pub fn open_archive(dir: &Option<PathBuf>) -> Result<CheckpointArchive> {
+    let dir = dir
+        .as_ref()
+        .ok_or_else(|| anyhow!("--archive-dir is not set"))?;
+    CheckpointArchive::open_read(dir)
+}
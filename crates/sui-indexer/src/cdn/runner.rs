@@ -65,7 +65,7 @@ pub async fn run(s3: &mut S3Store, pg: &mut PgConnection) -> Result<()> {
             img = Some("".to_string());
         }
 
-        update_image_url(pg, item.token_id.clone(), img)?;
+        update_image_url(pg, item.chain_id, item.token_id.clone(), img)?;
     }
 
     Ok(())
@@ -1,3 +1,7 @@
+use anyhow::anyhow;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Clone, Debug, Default)]
@@ -11,17 +15,63 @@ pub struct Config {
     )]
     pub node: String,
 
+    /// Sui network being indexed. Selects the chain id, cursor row, Redis
+    /// namespace and RabbitMQ routing prefix so several networks can share
+    /// one Postgres.
+    #[structopt(long, default_value = "mainnet", env = "SUI_NETWORK")]
+    pub network: Network,
+
     #[structopt(long, env = "DATABASE_URL")]
     pub postgres: String,
 
     #[structopt(long, default_value = "redis://127.0.0.1/", env = "REDIS")]
     pub redis: String,
 
-    #[structopt(long, env = "BOBYARD_CONTRACT")]
-    pub bob_yard: String,
+    /// BobYard package ids, comma separated: the original package and
+    /// each upgrade of it.
+    #[structopt(
+        long,
+        env = "BOBYARD_CONTRACT",
+        required = true,
+        use_delimiter = true
+    )]
+    pub bob_yard: Vec<String>,
+
+    /// `UpgradeCap` of the BobYard package, upgrades done with it are
+    /// picked up without a config change.
+    #[structopt(long, env = "BOBYARD_UPGRADE_CAP")]
+    pub bob_yard_upgrade_cap: Option<String>,
 
-    #[structopt(long, env = "OB_CONTRACT")]
-    pub origin_byte: String,
+    /// Module of the BobYard packages declaring the marketplace events,
+    /// events of its other modules are ignored.
+    #[structopt(long, default_value = "marketplace", env = "BOBYARD_MODULE")]
+    pub bob_yard_module: String,
+
+    /// Origin Byte package ids, comma separated.
+    #[structopt(
+        long,
+        env = "OB_CONTRACT",
+        required = true,
+        use_delimiter = true
+    )]
+    pub origin_byte: Vec<String>,
+
+    #[structopt(long, env = "OB_UPGRADE_CAP")]
+    pub origin_byte_upgrade_cap: Option<String>,
+
+    /// Origin Byte liquidity layer package ids emitting the orderbook
+    /// events, when they differ from `OB_CONTRACT`.
+    #[structopt(long, env = "OB_ORDERBOOK_CONTRACT", use_delimiter = true)]
+    pub origin_byte_orderbook: Vec<String>,
+
+    #[structopt(long, env = "OB_ORDERBOOK_UPGRADE_CAP")]
+    pub origin_byte_orderbook_upgrade_cap: Option<String>,
+
+    /// NFT types indexed as tokens even without a Display, comma separated.
+    /// A trailing `*` matches every type starting with the rest, e.g.
+    /// `0x5::nft::*`.
+    #[structopt(long, env = "NFT_TYPES", use_delimiter = true)]
+    pub nft_types: Vec<String>,
 
     #[structopt(
         long,
@@ -29,6 +79,124 @@ pub struct Config {
         env = "RABBITMQ_URI"
     )]
     pub mq: String,
+
+    /// Upper bound of checkpoints downloaded in one batch. The effective
+    /// window grows towards it while the indexer is behind the fullnode and
+    /// shrinks when it is close to the tip or the fullnode is failing.
     #[structopt(long, default_value = "25")]
     pub batch_index: u64,
+
+    /// Number of downloaded batches allowed to wait for the writer before
+    /// the downloader blocks.
+    #[structopt(long, default_value = "4", env = "QUEUE_CAPACITY")]
+    pub queue_capacity: usize,
+
+    /// Checkpoint time, in milliseconds, between two sweeps expiring the
+    /// listings and offers past their `expire_time`. Sweeps are aligned to
+    /// multiples of it.
+    #[structopt(long, default_value = "60000", env = "EXPIRY_SWEEP_MS")]
+    pub expiry_sweep_ms: i64,
+
+    /// Store every downloaded checkpoint as a compressed file under this
+    /// directory, indexed by sequence number.
+    #[structopt(long, env = "ARCHIVE_DIR", parse(from_os_str))]
+    pub archive_dir: Option<PathBuf>,
+
+    /// Read checkpoints from an archive directory written with
+    /// `--archive-dir` instead of the fullnode.
+    #[structopt(long, env = "CHECKPOINT_SOURCE", parse(from_os_str))]
+    pub source: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(StructOpt, Clone, Debug)]
+pub enum Command {
+    /// Re-index the closed checkpoint range `from..=to` without moving the
+    /// live cursor.
+    Backfill {
+        #[structopt(long)]
+        from: u64,
+        #[structopt(long)]
+        to: u64,
+    },
+    /// Check that `from..=to` is fully archived under `--archive-dir` and
+    /// that every file matches its index entry.
+    VerifyArchive {
+        #[structopt(long)]
+        from: u64,
+        #[structopt(long)]
+        to: u64,
+    },
+    /// Re-index the checkpoints with open `failed_items` and resolve the
+    /// items that parse now.
+    RetryFailed,
+    /// Parse the stored attributes of every token into `token_attributes`,
+    /// for tokens indexed before traits were kept.
+    BackfillAttributes {
+        #[structopt(long, default_value = "1000")]
+        batch_size: i64,
+    },
+    /// Recount the token counters of every collection.
+    BackfillCounters {
+        #[structopt(long, default_value = "100")]
+        batch_size: i64,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Devnet,
+    Localnet,
+}
+
+impl Default for Network {
+    fn default() -> Self { Network::Mainnet }
+}
+
+impl Network {
+    /// Value stored in the `chain_id` column of every table.
+    pub fn chain_id(&self) -> i64 {
+        match self {
+            Network::Mainnet => 1,
+            Network::Testnet => 2,
+            Network::Devnet => 3,
+            Network::Localnet => 4,
+        }
+    }
+
+    pub fn redis_key(&self, key: &str) -> String { format!("{}:{}", self, key) }
+
+    pub fn routing_key(&self, key: &str) -> String {
+        format!("{}.{}", self, key)
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Devnet => "devnet",
+            Network::Localnet => "localnet",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "devnet" => Ok(Network::Devnet),
+            "localnet" => Ok(Network::Localnet),
+            _ => Err(anyhow!("Unknown network: {}", s)),
+        }
+    }
 }
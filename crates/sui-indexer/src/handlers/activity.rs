@@ -1,17 +1,25 @@
 use crate::handlers::event::bobyard_event::BobYardEvent;
-use crate::models::activities::{Activity, ActivityType};
+use crate::handlers::{CheckpointHandler, CheckpointState, HandlerContext};
+use crate::indexer::CheckpointData;
+use crate::models::activities::{batch_insert, Activity, ActivityType};
 use crate::models::tokens::Token;
 use crate::ObjectStatus;
+use anyhow::Result;
+use diesel::PgConnection;
+use sui_sdk::types::event::EventID;
 
 use super::event::EventIndex;
 
+/// Marketplace activities for BobYard events and a transfer activity for
+/// every other changed token that has no activity in `existing` yet.
 pub fn parse_tokens_activity(
-    events: &Vec<EventIndex>,
+    events: &Vec<(EventID, EventIndex)>,
     tokens: &Vec<(ObjectStatus, (Token, String))>,
+    existing: &Vec<Activity>,
 ) -> Vec<Activity> {
     let bob_yard_events = events
         .iter()
-        .filter_map(|e| {
+        .filter_map(|(_, e)| {
             if let EventIndex::BobYard(bob_yard_event) = e {
                 Some(bob_yard_event.clone())
             } else {
@@ -50,7 +58,8 @@ pub fn parse_tokens_activity(
                         );
                         list_act.from_address = Some(token.1.clone());
                         list_act.to_address = Some(token.1.clone());
-                        list_act.token_amount = list.ask.parse().unwrap();
+                        list_act.token_amount = list.ask;
+                        list_act.coin_type = Some(list.coin_type.clone());
 
                         activity.push(list_act);
                     }
@@ -65,7 +74,8 @@ pub fn parse_tokens_activity(
                         );
                         list_act.from_address = Some(token.1.clone());
                         list_act.to_address = Some(token.1.clone());
-                        list_act.token_amount = delist.ask.parse().unwrap();
+                        list_act.token_amount = delist.ask;
+                        list_act.coin_type = Some(delist.coin_type.clone());
 
                         activity.push(list_act);
                     }
@@ -81,7 +91,8 @@ pub fn parse_tokens_activity(
                         );
                         list_act.from_address = Some(buy.owner.clone());
                         list_act.to_address = Some(buy.buyer.clone());
-                        list_act.token_amount = buy.ask.parse().unwrap();
+                        list_act.token_amount = buy.ask;
+                        list_act.coin_type = Some(buy.coin_type.clone());
                         activity.push(list_act);
                     }
                 });
@@ -96,22 +107,41 @@ pub fn parse_tokens_activity(
                         );
                         list_act.from_address = Some(buy.owner.clone());
                         list_act.to_address = Some(buy.buyer.clone());
-                        list_act.token_amount =
-                            buy.offer_amount.parse().unwrap();
+                        list_act.token_amount = buy.offer_amount;
+                        list_act.coin_type = Some(buy.coin_type.clone());
                         activity.push(list_act);
                     }
                 });
             }
 
-            BobYardEvent::MakeOffer(_) => {}
-            BobYardEvent::CancelOffer(_) => {}
+            BobYardEvent::AcceptCollectionOffer(buy) => {
+                let _ = changed_tokens.iter().for_each(|token| {
+                    if token.0.token_id == buy.item_id {
+                        let mut list_act = Activity::new_from_token_with_type(
+                            ActivityType::Sold,
+                            &token,
+                        );
+                        list_act.from_address = Some(buy.owner.clone());
+                        list_act.to_address = Some(buy.buyer.clone());
+                        list_act.token_amount = buy.offer_amount;
+                        list_act.coin_type = Some(buy.coin_type.clone());
+                        activity.push(list_act);
+                    }
+                });
+            }
+
+            // offers change no token, see `bobyard_event::offer_activities`.
+            BobYardEvent::MakeOffer(_)
+            | BobYardEvent::CancelOffer(_)
+            | BobYardEvent::CollectionOffer(_)
+            | BobYardEvent::CancelCollectionOffer(_) => {}
         };
     }
 
     changed_tokens.iter().for_each(|token| {
         let mk_event = activity.clone();
         let mut have = false;
-        mk_event.iter().for_each(|e| {
+        mk_event.iter().chain(existing.iter()).for_each(|e| {
             if e.token_data_id_hash == token.0.token_id {
                 have = true;
             }
@@ -127,3 +157,32 @@ pub fn parse_tokens_activity(
 
     activity
 }
+
+/// Derives marketplace and transfer activities from the tokens and events
+/// parsed earlier in the chain, then stores every collected activity.
+/// Register it after the handlers that produce activities.
+pub struct ActivityHandler;
+
+impl CheckpointHandler for ActivityHandler {
+    fn name(&self) -> &str { "activity" }
+
+    fn handle(
+        &mut self,
+        _ctx: &mut HandlerContext,
+        _checkpoint: &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let activities = parse_tokens_activity(
+            &state.events,
+            &state.tokens,
+            &state.activities,
+        );
+        state.activities.extend(activities);
+
+        if state.activities.len() > 0 {
+            batch_insert(conn, &state.activities)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::Result;
+use diesel::prelude::*;
+use serde_json::Value;
+use tracing::info;
+
+use crate::models::token_attributes::{self, TokenAttribute};
+use crate::models::tokens::{self, Token};
+
+/// Display keys describing the token rather than one of its traits.
+const DISPLAY_KEYS: [&str; 10] = [
+    "name",
+    "description",
+    "image_url",
+    "img_url",
+    "url",
+    "thumbnail_url",
+    "link",
+    "project_url",
+    "creator",
+    "id",
+];
+/// Longest `trait_type` kept, nested keys are joined with `.`.
+const MAX_TRAIT_LEN: usize = 255;
+/// Longest value kept, in bytes. Values are part of the key, longer ones
+/// are descriptions rather than traits.
+const MAX_VALUE_LEN: usize = 255;
+
+/// Values holding JSON, like Display `tags`, are parsed in place.
+fn parse_embedded(value: &Value) -> Option<Value> {
+    let s = value.as_str()?.trim();
+    if !(s.starts_with('[') || s.starts_with('{')) {
+        return None;
+    }
+    serde_json::from_str::<Value>(s).ok()
+}
+
+/// `{ "trait_type": .., "value": .. }` style entries, also as `key` or
+/// `name`.
+fn trait_entry(
+    fields: &serde_json::Map<String, Value>,
+) -> Option<(&str, &Value)> {
+    let name = ["trait_type", "key", "name"]
+        .iter()
+        .find_map(|k| fields.get(*k).and_then(|n| n.as_str()))?;
+    Some((name, fields.get("value")?))
+}
+
+fn flatten(trait_type: &str, value: &Value, out: &mut Vec<(String, String)>) {
+    if let Some(parsed) = parse_embedded(value) {
+        return flatten(trait_type, &parsed, out);
+    }
+    match value {
+        Value::Null => {}
+        Value::String(s) => out.push((trait_type.to_string(), s.clone())),
+        Value::Number(n) => out.push((trait_type.to_string(), n.to_string())),
+        Value::Bool(b) => out.push((trait_type.to_string(), b.to_string())),
+        Value::Array(values) => {
+            values.iter().for_each(|v| flatten(trait_type, v, out))
+        }
+        Value::Object(fields) => {
+            // Move structs nested in the parsed content keep their `fields`.
+            if let Some(inner) = fields.get("fields") {
+                return flatten(trait_type, inner, out);
+            }
+            if let Some((name, value)) = trait_entry(fields) {
+                return flatten(&join(trait_type, name), value, out);
+            }
+            for (k, v) in fields.iter() {
+                flatten(&join(trait_type, k), v, out);
+            }
+        }
+    }
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+/// `(trait_type, value)` pairs of a token's `attributes` JSON, either its
+/// Display map or the attributes of its Move fields. A Display `attributes`
+/// entry is used alone when present, otherwise every non-descriptive key is
+/// a trait. Arrays give one pair per element and nested objects are
+/// flattened into dotted trait types. Pairs with an oversized trait type or
+/// value are dropped.
+pub fn parse_attributes(attributes: &str) -> Vec<(String, String)> {
+    let value = match serde_json::from_str::<Value>(attributes) {
+        Ok(value) => value,
+        Err(_) => return vec![],
+    };
+    let mut out = vec![];
+    match value.as_object() {
+        Some(fields) => match fields.get("attributes") {
+            Some(attributes) => flatten("", attributes, &mut out),
+            None => fields
+                .iter()
+                .filter(|(k, _)| !DISPLAY_KEYS.contains(&k.as_str()))
+                .for_each(|(k, v)| flatten(k, v, &mut out)),
+        },
+        None => flatten("", &value, &mut out),
+    }
+    out.retain(|(trait_type, value)| {
+        !trait_type.is_empty()
+            && trait_type.len() <= MAX_TRAIT_LEN
+            && value.len() <= MAX_VALUE_LEN
+    });
+    out.sort();
+    out.dedup();
+    out
+}
+
+/// The `token_attributes` rows of `token`.
+pub fn attribute_rows(token: &Token) -> Vec<TokenAttribute> {
+    token
+        .attributes
+        .as_deref()
+        .map(parse_attributes)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(trait_type, value)| TokenAttribute {
+            chain_id: token.chain_id,
+            token_id: token.token_id.clone(),
+            collection_id: token.collection_id.clone(),
+            trait_type,
+            value,
+            version: token.version,
+        })
+        .collect()
+}
+
+/// Parse the stored attributes of every token of the chain into
+/// `token_attributes`, `batch_size` tokens per transaction. Tokens burned
+/// or moved to a newer version since the page was read are skipped, and
+/// rows from a newer version are kept, so it can run next to the indexer.
+pub fn backfill(
+    conn: &mut PgConnection,
+    chain_id: i64,
+    batch_size: i64,
+) -> Result<usize> {
+    let mut after = String::new();
+    let mut parsed = 0;
+    loop {
+        let page = tokens::query_page(conn, chain_id, &after, batch_size)?;
+        let last = match page.last() {
+            Some(token) => token.token_id.clone(),
+            None => break,
+        };
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            for token in page.iter() {
+                token_attributes::replace(
+                    conn,
+                    chain_id,
+                    &token.token_id,
+                    token.version,
+                    &attribute_rows(token),
+                )?;
+            }
+            Ok(())
+        })?;
+        parsed += page.len();
+        info!(tokens = parsed, last = last, "Attributes backfilled");
+        after = last;
+    }
+    info!(tokens = parsed, "Attributes backfill finished");
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(t, v)| (t.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_display_attributes_entries() {
+        let attributes = r#"{
+            "name": "Ninja #1",
+            "background": "Red",
+            "attributes": [
+                { "trait_type": "Hat", "value": "Cap" },
+                { "key": "Eyes", "value": "Blue" },
+                { "name": "Eyes", "value": "Blue" }
+            ]
+        }"#;
+        assert_eq!(
+            parse_attributes(attributes),
+            pairs(&[("Eyes", "Blue"), ("Hat", "Cap")])
+        );
+    }
+
+    #[test]
+    fn keeps_non_descriptive_display_keys() {
+        let attributes = r#"{
+            "name": "Ninja #1",
+            "description": "A ninja",
+            "image_url": "ipfs://ninja",
+            "level": 3,
+            "shiny": true,
+            "missing": null,
+            "clan": "Leaf"
+        }"#;
+        assert_eq!(
+            parse_attributes(attributes),
+            pairs(&[("clan", "Leaf"), ("level", "3"), ("shiny", "true")])
+        );
+    }
+
+    #[test]
+    fn parses_embedded_json_and_arrays() {
+        let attributes =
+            r#"{ "tags": "[\"fire\", \"rare\"]", "stats": "{\"hp\": 9}" }"#;
+        assert_eq!(
+            parse_attributes(attributes),
+            pairs(&[("stats.hp", "9"), ("tags", "fire"), ("tags", "rare")])
+        );
+    }
+
+    #[test]
+    fn flattens_nested_move_structs() {
+        let attributes = r#"{
+            "power": { "type": "0x5::nft::Power", "fields": { "attack": "7", "defense": "2" } }
+        }"#;
+        assert_eq!(
+            parse_attributes(attributes),
+            pairs(&[("power.attack", "7"), ("power.defense", "2")])
+        );
+    }
+
+    #[test]
+    fn drops_oversized_traits_and_values() {
+        let long_value = "v".repeat(MAX_VALUE_LEN + 1);
+        let long_trait = "t".repeat(MAX_TRAIT_LEN + 1);
+        let kept = "v".repeat(MAX_VALUE_LEN);
+        let attributes = serde_json::json!({
+            "bio": long_value,
+            long_trait: "x",
+            "motto": kept,
+        })
+        .to_string();
+        assert_eq!(
+            parse_attributes(&attributes),
+            vec![("motto".to_string(), kept)]
+        );
+    }
+
+    #[test]
+    fn ignores_invalid_json() {
+        assert!(parse_attributes("not json").is_empty());
+        assert!(parse_attributes("").is_empty());
+        assert!(parse_attributes("{}").is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::PgConnection;
+use tracing::info;
+
+use crate::handlers::{CheckpointHandler, CheckpointState, HandlerContext};
+use crate::indexer::receiver::{IndexingMessage, Message};
+use crate::indexer::CheckpointData;
+use crate::models::activities::{Activity, ActivityType};
+use crate::models::lists::{self, ListType};
+use crate::models::{token_attributes, token_ownership_history, tokens};
+use crate::{get_deleted_db_objects, ObjectStatus};
+
+/// Marks deleted and unwrapped-then-deleted tokens burned, cancels their
+/// open listings, records a burn activity and publishes `token.delete`.
+/// Deleted objects are never downloaded, so they are read from the
+/// transaction effects and matched against the stored tokens.
+pub struct BurnHandler;
+
+impl CheckpointHandler for BurnHandler {
+    fn name(&self) -> &str { "burn" }
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        (checkpoint, transactions, _, _): &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let chain_id = ctx.network.chain_id();
+        for tx in transactions.iter() {
+            for (status, object, sender, timestamp) in
+                get_deleted_db_objects(tx)?
+            {
+                if status != ObjectStatus::Deleted
+                    && status != ObjectStatus::UnwrappedThenDeleted
+                {
+                    continue;
+                }
+                let token_id = object.object_id.to_string();
+                let version = object.version.value() as i64;
+                let token = match tokens::burn(
+                    conn,
+                    chain_id,
+                    &token_id,
+                    version,
+                    &tx.digest.to_string(),
+                    timestamp as i64,
+                )? {
+                    Some(token) => token,
+                    None => continue,
+                };
+                token_attributes::delete(conn, chain_id, &token_id)?;
+                token_ownership_history::close(
+                    conn,
+                    chain_id,
+                    &token_id,
+                    version,
+                    checkpoint.sequence_number as i64,
+                    timestamp as i64,
+                )?;
+                let canceled = lists::close_by_token(
+                    conn,
+                    chain_id,
+                    &token_id,
+                    ListType::Canceled,
+                )?;
+                info!(
+                    checkpoint = checkpoint.sequence_number,
+                    token = token_id,
+                    listings = canceled.len(),
+                    "Token burned"
+                );
+
+                let mut burned = Activity::new_from_token_with_type(
+                    ActivityType::Burned,
+                    &(token.clone(), sender.clone()),
+                );
+                burned.to_address = None;
+                burned.transaction_timestamp =
+                    NaiveDateTime::from_timestamp_millis(timestamp as i64)
+                        .unwrap();
+                state.activities.push(burned);
+                state.messages.push(IndexingMessage::Token((
+                    Message::Delete,
+                    token.clone(),
+                )));
+                state.tokens.push((status, (token, sender)));
+            }
+        }
+        Ok(())
+    }
+}
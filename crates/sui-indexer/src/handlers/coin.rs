@@ -0,0 +1,99 @@
+use anyhow::{anyhow, Result};
+use diesel::PgConnection;
+use sui_sdk::rpc_types::{Checkpoint, SuiObjectData, SuiParsedData};
+use tracing::warn;
+
+use crate::handlers::{
+    failed_object, CheckpointHandler, CheckpointState, HandlerContext,
+};
+use crate::indexer::CheckpointData;
+use crate::models::coins::{batch_change, Coin};
+use crate::models::failed_items::FailedItem;
+use crate::ObjectStatus;
+
+const COIN_METADATA: &str = "0x2::coin::CoinMetadata<";
+
+pub fn parse_coins(
+    checkpoint: &Checkpoint,
+    object_changes: &Vec<(ObjectStatus, SuiObjectData, String, u64)>,
+    chain_id: i64,
+    failures: &mut Vec<FailedItem>,
+) -> Vec<Coin> {
+    object_changes
+        .iter()
+        .filter_map(|(_, obj, _, timestamp)| {
+            let object_type = obj.type_.as_ref()?.to_string();
+            let coin_type = object_type
+                .strip_prefix(COIN_METADATA)?
+                .strip_suffix(">")?
+                .to_string();
+            match parse_metadata(obj, coin_type, *timestamp) {
+                Ok(coin) => Some(coin),
+                Err(e) => {
+                    warn!(object_id = %obj.object_id, "Failed to parse CoinMetadata: {:#}", e);
+                    failures.push(failed_object(chain_id, checkpoint, obj, &e));
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn parse_metadata(
+    obj: &SuiObjectData,
+    coin_type: String,
+    timestamp: u64,
+) -> Result<Coin> {
+    let fields = match obj.content.as_ref() {
+        Some(SuiParsedData::MoveObject(parse_obj)) => {
+            parse_obj.fields.clone().to_json_value()
+        }
+        _ => return Err(anyhow!("CoinMetadata has no content")),
+    };
+    let decimals = fields["decimals"]
+        .as_i64()
+        .ok_or_else(|| anyhow!("CoinMetadata without decimals"))?;
+    let text = |name: &str| fields[name].as_str().map(|s| s.to_string());
+
+    Ok(Coin {
+        coin_type,
+        symbol: text("symbol"),
+        name: text("name"),
+        decimals: Some(decimals as i32),
+        // an `Option<Url>`, either the url or the `Url` struct.
+        icon_url: text("icon_url").or_else(|| {
+            fields["icon_url"]["url"].as_str().map(|s| s.to_string())
+        }),
+        metadata_id: Some(obj.object_id.to_string()),
+        version: obj.version.value() as i64,
+        created_at: timestamp as i64,
+        updated_at: timestamp as i64,
+    })
+}
+
+/// Fills `coins` from `0x2::coin::CoinMetadata<T>` objects, giving prices
+/// in any coin their symbol and decimals.
+pub struct CoinHandler;
+
+impl CheckpointHandler for CoinHandler {
+    fn name(&self) -> &str { "coin" }
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        (checkpoint, _, object_changed, _): &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let coins = parse_coins(
+            checkpoint,
+            object_changed,
+            ctx.network.chain_id(),
+            &mut state.failures,
+        );
+        if coins.len() > 0 {
+            batch_change(conn, &coins)?;
+        }
+        Ok(())
+    }
+}
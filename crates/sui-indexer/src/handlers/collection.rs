@@ -1,110 +1,172 @@
+use crate::config::Network;
+use crate::handlers::transfer_policy::fill_collection_royalties;
+use crate::handlers::{
+    failed_object, CheckpointHandler, CheckpointState, HandlerContext,
+};
+use crate::indexer::receiver::{IndexingMessage, Message};
+use crate::indexer::CheckpointData;
 use crate::models::activities::{Activity, ActivityType};
-use crate::models::collections::Collection;
+use crate::models::collection_display_versions::{
+    self, CollectionDisplayVersion,
+};
+use crate::models::collections::{batch_change, batch_insert, Collection};
+use crate::models::failed_items::FailedItem;
 use crate::utils::json_to_kv_map;
 use crate::ObjectStatus;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use chrono::Utc;
+use diesel::PgConnection;
 
 use redis::Commands;
 use std::collections::HashMap;
-use sui_sdk::rpc_types::{SuiObjectData, SuiParsedData};
+use sui_sdk::rpc_types::{Checkpoint, SuiObjectData, SuiParsedData};
+use tracing::warn;
 
 pub fn parse_collection(
+    checkpoint: &Checkpoint,
     object_changes: &Vec<(ObjectStatus, SuiObjectData, String, u64)>,
+    network: &Network,
     con: &mut redis::Connection,
     coll_set: &mut HashMap<String, String>,
+    versions: &mut Vec<CollectionDisplayVersion>,
+    failures: &mut Vec<FailedItem>,
 ) -> Result<Vec<(ObjectStatus, Collection)>> {
-    Ok(object_changes
-        .into_iter()
-        .filter_map(|(status, obj, sender, timestamp)| {
-            let object_type = obj.type_.as_ref().unwrap().clone().to_string();
-            if object_type.contains("0x2::display::Display<") {
-                let object_type = object_type
-                    .strip_prefix("0x2::display::Display<")
-                    .unwrap()
-                    .strip_suffix(">")
-                    .unwrap()
-                    .to_string();
-                let object_id = obj.object_id.to_string();
-
-                let _: () = con
-                    .hset("collections", object_type.clone(), object_id.clone())
-                    .unwrap();
-                coll_set.insert(object_type.clone(), object_id.clone());
-
-                let content = obj.content.as_ref().unwrap();
-                let kv = match content {
-                    SuiParsedData::MoveObject(parse_obj) => {
-                        parse_obj.fields.clone().to_json_value()
-                    }
-                    SuiParsedData::Package(_) => {
-                        unreachable!("Package should not be in display")
-                    }
-                };
-
-                let fields = &kv["fields"]["contents"];
-                let kv_set = json_to_kv_map(fields);
-
-                let image_url = kv_set
-                    .get(&"image_url".to_string())
-                    .unwrap_or(&"".to_string())
-                    .clone();
-                let description = kv_set
-                    .get(&"description".to_string())
-                    .unwrap_or(&"".to_string())
-                    .clone();
-                let project_url =
-                    kv_set.get(&"project_url".to_string()).cloned();
-
-                //let project_url =
-                // kv_set.get(&"project_url".to_string()).unwrap_or(&"".
-                // to_string()).clone();
-                //let creator = kv_set.get(&"creator".to_string()).cloned();
-
-                let collection_data_in_json =
-                    serde_json::to_string(&kv_set).unwrap();
-                let collection_name =
-                    object_type.split("::").last().unwrap().to_string();
-
-                let tx: Option<String> =
-                    if let Some(ok) = obj.previous_transaction {
-                        Some(ok.to_string())
-                    } else {
-                        None
-                    };
-
-                let collection = Collection {
-                    chain_id: 1,
-                    slug: None,
-                    collection_id: object_id,
-                    collection_type: object_type,
-                    creator_address: sender.clone(),
-                    royaltie: None,
-                    display_name: None,
-                    website: project_url,
-                    discord: None,
-                    twitter: None,
-                    icon: None,
-                    banner: None,
-                    collection_name,
-                    description,
-                    supply: 0,
-                    version: obj.version.value() as i64,
-                    metadata_uri: image_url,
-                    metadata: collection_data_in_json,
-                    tx,
-                    verify: false,
-                    last_metadata_sync: Utc::now()
-                        .naive_utc()
-                        .timestamp_millis(),
-                    created_at: *timestamp as i64,
-                    updated_at: *timestamp as i64,
-                };
-                return Some((status.clone(), collection));
+    let mut collections = vec![];
+    for (status, obj, sender, timestamp) in object_changes {
+        let object_type = match &obj.type_ {
+            Some(object_type) => object_type.to_string(),
+            None => continue,
+        };
+        let object_type = match object_type
+            .strip_prefix("0x2::display::Display<")
+            .and_then(|t| t.strip_suffix(">"))
+        {
+            Some(object_type) => object_type.to_string(),
+            None => continue,
+        };
+        let object_id = obj.object_id.to_string();
+
+        let _: () = con.hset(
+            network.redis_key("collections"),
+            object_type.clone(),
+            object_id.clone(),
+        )?;
+        coll_set.insert(object_type.clone(), object_id.clone());
+
+        match parse_display(
+            obj,
+            object_type,
+            object_id,
+            network,
+            sender,
+            *timestamp,
+        ) {
+            Ok((collection, display_version)) => {
+                versions.push(CollectionDisplayVersion {
+                    chain_id: network.chain_id(),
+                    collection_id: collection.collection_id.clone(),
+                    version: collection.version,
+                    display_version,
+                    metadata: collection.metadata.clone(),
+                    tx: collection.tx.clone(),
+                    created_at: collection.updated_at,
+                });
+                collections.push((*status, collection));
             }
-            None
-        })
-        .collect::<Vec<(ObjectStatus, Collection)>>())
+            Err(e) => {
+                warn!(object_id = %obj.object_id, "Failed to parse Display: {:#}", e);
+                failures.push(failed_object(
+                    network.chain_id(),
+                    checkpoint,
+                    obj,
+                    &e,
+                ));
+            }
+        }
+    }
+
+    Ok(collections)
+}
+
+fn parse_display(
+    obj: &SuiObjectData,
+    object_type: String,
+    object_id: String,
+    network: &Network,
+    sender: &String,
+    timestamp: u64,
+) -> Result<(Collection, i64)> {
+    let kv = match obj.content.as_ref() {
+        Some(SuiParsedData::MoveObject(parse_obj)) => {
+            parse_obj.fields.clone().to_json_value()
+        }
+        Some(SuiParsedData::Package(_)) => {
+            bail!("Package should not be in display")
+        }
+        None => bail!("Display object has no content"),
+    };
+
+    let display_version = kv["version"].as_i64().unwrap_or_default();
+    let fields = &kv["fields"]["contents"];
+    let kv_set = json_to_kv_map(fields)?;
+
+    let image_url = kv_set
+        .get(&"image_url".to_string())
+        .unwrap_or(&"".to_string())
+        .clone();
+    let description = kv_set
+        .get(&"description".to_string())
+        .unwrap_or(&"".to_string())
+        .clone();
+    let project_url = kv_set.get(&"project_url".to_string()).cloned();
+
+    //let project_url =
+    // kv_set.get(&"project_url".to_string()).unwrap_or(&"".
+    // to_string()).clone();
+    //let creator = kv_set.get(&"creator".to_string()).cloned();
+
+    let collection_data_in_json = serde_json::to_string(&kv_set)?;
+    let collection_name = object_type
+        .split("::")
+        .last()
+        .unwrap_or_default()
+        .to_string();
+
+    let tx: Option<String> = if let Some(ok) = obj.previous_transaction {
+        Some(ok.to_string())
+    } else {
+        None
+    };
+
+    let collection = Collection {
+        chain_id: network.chain_id() as i32,
+        slug: None,
+        collection_id: object_id,
+        collection_type: object_type,
+        creator_address: sender.clone(),
+        royaltie: None,
+        display_name: None,
+        website: project_url,
+        discord: None,
+        twitter: None,
+        icon: None,
+        banner: None,
+        collection_name,
+        description,
+        supply: 0,
+        version: obj.version.value() as i64,
+        metadata_uri: image_url,
+        metadata: collection_data_in_json,
+        tx,
+        verify: false,
+        last_metadata_sync: Utc::now().naive_utc().timestamp_millis(),
+        created_at: timestamp as i64,
+        updated_at: timestamp as i64,
+        minted: 0,
+        burned: 0,
+        holders: 0,
+    };
+    Ok((collection, display_version))
 }
 
 pub fn collection_indexer_work(
@@ -137,3 +199,88 @@ pub fn collection_indexer_work(
 
     Ok((insert_collections, created_activities))
 }
+
+/// Collections whose Display was edited.
+pub fn collection_updates(
+    collections: &Vec<(ObjectStatus, Collection)>,
+) -> Vec<Collection> {
+    collections
+        .iter()
+        .filter(|(status, _)| {
+            *status == ObjectStatus::Mutated
+                || *status == ObjectStatus::Unwrapped
+        })
+        .map(|(_, collection)| collection.clone())
+        .collect()
+}
+
+/// Indexes `0x2::display::Display<T>` objects as collections, keeping
+/// every Display version and following later edits.
+pub struct CollectionHandler {
+    redis: redis::Connection,
+}
+
+impl CollectionHandler {
+    pub fn new(redis: redis::Connection) -> Self { Self { redis } }
+}
+
+impl CheckpointHandler for CollectionHandler {
+    fn name(&self) -> &str { "collection" }
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        (checkpoint, _, object_changed, _): &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let mut versions = vec![];
+        let mut collections = parse_collection(
+            checkpoint,
+            object_changed,
+            &ctx.network,
+            &mut self.redis,
+            &mut ctx.collection_types,
+            &mut versions,
+            &mut state.failures,
+        )?;
+        fill_collection_royalties(
+            conn,
+            ctx.network.chain_id(),
+            &mut collections,
+        )?;
+
+        // edits are announced below, once they are known to be newer.
+        for (msg, collection) in collections.iter().filter(|(status, _)| {
+            *status != ObjectStatus::Mutated
+                && *status != ObjectStatus::Unwrapped
+        }) {
+            state.messages.push(IndexingMessage::Collection((
+                (*msg).into(),
+                collection.clone(),
+            )));
+        }
+
+        let (insert_collections, created_activities) =
+            collection_indexer_work(&collections)?;
+        if insert_collections.len() > 0 {
+            batch_insert(conn, &insert_collections)?;
+        }
+        let updates = collection_updates(&collections);
+        if updates.len() > 0 {
+            for collection in batch_change(conn, &updates)? {
+                state.messages.push(IndexingMessage::Collection((
+                    Message::Update,
+                    collection,
+                )));
+            }
+        }
+        if versions.len() > 0 {
+            collection_display_versions::batch_insert(conn, &versions)?;
+        }
+
+        state.activities.extend(created_activities);
+        state.collections = collections;
+        Ok(())
+    }
+}
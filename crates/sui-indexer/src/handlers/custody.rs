@@ -0,0 +1,202 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::PgConnection;
+use serde_json::Value;
+use sui_sdk::rpc_types::SuiTransactionBlockData::V1;
+use sui_sdk::rpc_types::{
+    SuiCommand, SuiObjectData, SuiParsedData, SuiTransactionBlockKind,
+    SuiTransactionBlockResponse,
+};
+use tracing::info;
+
+use crate::handlers::transfer_policy::normalize_type;
+use crate::handlers::{CheckpointHandler, CheckpointState, HandlerContext};
+use crate::indexer::receiver::{IndexingMessage, Message};
+use crate::indexer::CheckpointData;
+use crate::models::activities::{Activity, ActivityType};
+use crate::models::token_custody::{self, TokenCustody};
+use crate::models::tokens::{self, TokenStatus};
+use crate::{get_deleted_db_objects, ObjectStatus};
+
+const FRAMEWORK_PACKAGES: [&str; 3] = ["0x1", "0x2", "0x3"];
+
+/// `package::module` of the first Move call outside the framework, or of
+/// the first one when the transaction only calls the framework.
+fn protocol(tx: &SuiTransactionBlockResponse) -> Option<String> {
+    let data = match tx.transaction.as_ref()?.data {
+        V1(ref v1) => v1,
+    };
+    let calls = match &data.transaction {
+        SuiTransactionBlockKind::ProgrammableTransaction(pt) => pt
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                SuiCommand::MoveCall(call) => Some(format!(
+                    "0x{}",
+                    normalize_type(&format!(
+                        "{}::{}",
+                        call.package, call.module
+                    ))
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        _ => return None,
+    };
+    calls
+        .iter()
+        .find(|call| {
+            !FRAMEWORK_PACKAGES
+                .iter()
+                .any(|p| call.starts_with(&format!("{}::", p)))
+        })
+        .or(calls.first())
+        .cloned()
+}
+
+fn holds(value: &Value, id: &str) -> bool {
+    match value {
+        Value::String(s) => s == id,
+        Value::Array(values) => values.iter().any(|v| holds(v, id)),
+        Value::Object(fields) => fields.values().any(|v| holds(v, id)),
+        _ => false,
+    }
+}
+
+/// Object changed by `tx` whose content embeds `token_id`.
+fn find_wrapper<'a>(
+    objects: &'a Vec<(ObjectStatus, SuiObjectData, String, u64)>,
+    tx: &SuiTransactionBlockResponse,
+    token_id: &str,
+) -> Option<&'a SuiObjectData> {
+    objects
+        .iter()
+        .filter(|(_, obj, _, _)| obj.previous_transaction == Some(tx.digest))
+        .map(|(_, obj, _, _)| obj)
+        .find(|obj| match obj.content.as_ref() {
+            Some(SuiParsedData::MoveObject(parse_obj)) => {
+                holds(&parse_obj.fields.clone().to_json_value(), token_id)
+            }
+            _ => false,
+        })
+}
+
+/// Tracks which object or protocol holds each wrapped token in
+/// `token_custody`, with a wrap and unwrap activity and message for every
+/// change.
+/// Transactions are replayed in order so a token wrapped and unwrapped in
+/// the same checkpoint ends up released. Register it after the burn
+/// handler.
+pub struct CustodyHandler;
+
+impl CheckpointHandler for CustodyHandler {
+    fn name(&self) -> &str { "custody" }
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        (checkpoint, transactions, object_changed, _): &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let chain_id = ctx.network.chain_id();
+        for tx in transactions.iter() {
+            let digest = tx.digest.to_string();
+            let timestamp = tx.timestamp_ms.unwrap_or_default() as i64;
+            let time = NaiveDateTime::from_timestamp_millis(timestamp).unwrap();
+
+            for (status, object, sender, _) in get_deleted_db_objects(tx)? {
+                if status != ObjectStatus::Wrapped {
+                    continue;
+                }
+                let token_id = object.object_id.to_string();
+                let stored = tokens::query_token(conn, chain_id, &token_id)?;
+                let token = match stored {
+                    Some(token) if token.status != TokenStatus::DELETE => token,
+                    _ => continue,
+                };
+                let wrapper = find_wrapper(object_changed, tx, &token_id);
+                let custody = TokenCustody {
+                    chain_id,
+                    token_id: token_id.clone(),
+                    custodian_id: wrapper.map(|w| w.object_id.to_string()),
+                    custodian_type: wrapper
+                        .and_then(|w| w.type_.as_ref())
+                        .map(|t| t.to_string()),
+                    protocol: protocol(tx),
+                    owner_address: token.owner_address.clone(),
+                    version: object.version.value() as i64,
+                    tx_digest: digest.clone(),
+                    wrapped_at: timestamp,
+                    unwrapped_at: None,
+                    created_at: timestamp,
+                    updated_at: timestamp,
+                };
+                if token_custody::wrap(conn, &custody)? == 0 {
+                    continue;
+                }
+                info!(
+                    checkpoint = checkpoint.sequence_number,
+                    token = token_id,
+                    custodian = ?custody.custodian_id,
+                    protocol = ?custody.protocol,
+                    "Token wrapped"
+                );
+
+                let mut wrapped = Activity::new_from_token_with_type(
+                    ActivityType::Wrapped,
+                    &(token.clone(), sender.clone()),
+                );
+                wrapped.version = custody.version;
+                wrapped.property_version = custody.version;
+                wrapped.tx = digest.clone();
+                wrapped.from_address =
+                    token.owner_address.clone().or(Some(sender));
+                wrapped.to_address = custody.custodian_id.clone();
+                wrapped.transaction_timestamp = time;
+                state.activities.push(wrapped);
+                state
+                    .messages
+                    .push(IndexingMessage::Token((Message::Wrap, token)));
+            }
+
+            for (status, (token, sender)) in state.tokens.iter() {
+                if (*status != ObjectStatus::Unwrapped
+                    && *status != ObjectStatus::UnwrappedThenDeleted)
+                    || token.tx.as_deref() != Some(digest.as_str())
+                {
+                    continue;
+                }
+                // nothing held it, e.g. a replayed unwrap already released.
+                let custody = match token_custody::release(
+                    conn,
+                    chain_id,
+                    &token.token_id,
+                    token.version,
+                    &digest,
+                    timestamp,
+                )? {
+                    Some(custody) => custody,
+                    None => continue,
+                };
+                // a burn activity already covers unwrapped then deleted.
+                if *status == ObjectStatus::UnwrappedThenDeleted {
+                    continue;
+                }
+
+                let mut unwrapped = Activity::new_from_token_with_type(
+                    ActivityType::Unwrapped,
+                    &(token.clone(), sender.clone()),
+                );
+                unwrapped.from_address = custody.custodian_id;
+                unwrapped.transaction_timestamp = time;
+                state.activities.push(unwrapped);
+                state.messages.push(IndexingMessage::Token((
+                    Message::Unwrap,
+                    token.clone(),
+                )));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,68 +1,152 @@
+use crate::models::activities::{Activity, ActivityType};
+use crate::models::amount::Amount;
+use crate::models::coins::{self, SUI_COIN_TYPE};
 use crate::models::lists::ListType;
 use crate::models::offers::OfferType;
 use crate::models::orders::OrderType;
-use crate::models::{lists, offers, orders};
+use crate::models::{lists, offers, orders, tokens};
 
 use anyhow::Result;
 use chrono::{NaiveDateTime, Utc};
 use diesel::PgConnection;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use sui_sdk::rpc_types::SuiEvent;
+use sui_sdk::types::event::EventID;
+use sui_sdk::types::TypeTag;
 use tracing::info;
 
-use super::EventIndex;
+use super::{layout, EventIndex};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct List {
+    #[serde(deserialize_with = "layout::id")]
     pub list_id: String,
+    #[serde(deserialize_with = "layout::id")]
     pub list_item_id: String,
-    pub expire_time: String,
-    pub ask: String,
+    #[serde(deserialize_with = "layout::timestamp_ms")]
+    pub expire_time: i64,
+    pub ask: Amount,
+    /// Set from the event type, see `coin_type`.
+    #[serde(skip)]
+    pub coin_type: String,
+    #[serde(deserialize_with = "layout::address")]
     pub owner: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DeList {
+    #[serde(deserialize_with = "layout::id")]
     pub list_id: String,
+    #[serde(deserialize_with = "layout::id")]
     pub list_item_id: String,
-    pub expire_time: String,
-    pub ask: String,
+    #[serde(deserialize_with = "layout::timestamp_ms")]
+    pub expire_time: i64,
+    pub ask: Amount,
+    #[serde(skip)]
+    pub coin_type: String,
+    #[serde(deserialize_with = "layout::address")]
     pub owner: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Buy {
+    #[serde(deserialize_with = "layout::id")]
     pub list_id: String,
+    #[serde(deserialize_with = "layout::id")]
     pub item_id: String,
-    pub ask: String,
+    pub ask: Amount,
+    #[serde(skip)]
+    pub coin_type: String,
+    #[serde(deserialize_with = "layout::address")]
     pub owner: String,
+    #[serde(deserialize_with = "layout::address")]
     pub buyer: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AcceptOffer {
+    #[serde(deserialize_with = "layout::id")]
     pub offer_id: String,
+    #[serde(deserialize_with = "layout::id")]
     pub list_id: String,
+    #[serde(deserialize_with = "layout::id")]
     pub item_id: String,
-    pub offer_amount: String,
+    pub offer_amount: Amount,
+    #[serde(skip)]
+    pub coin_type: String,
+    #[serde(deserialize_with = "layout::address")]
     pub owner: String,
+    #[serde(deserialize_with = "layout::address")]
     pub buyer: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MakeOffer {
-    offer_id: String,
-    list_id: String,
-    offer_amount: String,
-    expire_time: String,
-    owner: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub offer_id: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub list_id: String,
+    pub offer_amount: Amount,
+    #[serde(skip)]
+    pub coin_type: String,
+    #[serde(deserialize_with = "layout::timestamp_ms")]
+    pub expire_time: i64,
+    #[serde(deserialize_with = "layout::address")]
+    pub owner: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CancelOffer {
-    offer_id: String,
-    list_id: String,
-    owner: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub offer_id: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub list_id: String,
+    #[serde(deserialize_with = "layout::address")]
+    pub owner: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionOffer {
+    #[serde(deserialize_with = "layout::id")]
+    pub offer_id: String,
+    pub offer_amount: Amount,
+    #[serde(skip)]
+    pub coin_type: String,
+    #[serde(deserialize_with = "layout::timestamp_ms")]
+    pub expire_time: i64,
+    #[serde(deserialize_with = "layout::address")]
+    pub owner: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct CollectionOfferWithType {
+    pub offer: CollectionOffer,
+    /// The `T` of `CollectionOfferEvent<T>`.
+    pub collection_type: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CancelCollectionOffer {
+    #[serde(deserialize_with = "layout::id")]
+    pub offer_id: String,
+    #[serde(deserialize_with = "layout::address")]
+    pub owner: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AcceptCollectionOffer {
+    #[serde(deserialize_with = "layout::id")]
+    pub offer_id: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub item_id: String,
+    pub offer_amount: Amount,
+    #[serde(skip)]
+    pub coin_type: String,
+    #[serde(deserialize_with = "layout::address")]
+    pub owner: String,
+    #[serde(deserialize_with = "layout::address")]
+    pub buyer: String,
 }
 
 impl From<BobYardEvent> for EventIndex {
@@ -77,28 +161,28 @@ pub enum BobYardEvent {
     AcceptOffer(AcceptOffer),
     MakeOffer(MakeOffer),
     CancelOffer(CancelOffer),
+    CollectionOffer(CollectionOfferWithType),
+    CancelCollectionOffer(CancelCollectionOffer),
+    AcceptCollectionOffer(AcceptCollectionOffer),
 }
 
 impl From<&List> for lists::List {
     fn from(list: &List) -> Self {
         lists::List {
-            chain_id: 1,
-            coin_id: 1,
+            chain_id: Default::default(),
+            coin_id: Default::default(),
             list_id: list.list_id.clone(),
             list_time: Utc::now().naive_utc(),
             token_id: list.list_item_id.clone(),
             seller_address: list.owner.clone(),
-            seller_value: list.ask.parse().unwrap(),
+            seller_value: list.ask,
             list_type: ListType::Listed,
             market_type: lists::MarketType::BobYard,
-            expire_time: Some(
-                NaiveDateTime::from_timestamp_millis(
-                    list.expire_time.parse().unwrap(),
-                )
-                .unwrap(),
-            ),
+            expire_time: NaiveDateTime::from_timestamp_millis(list.expire_time),
             created_at: Some(Utc::now().naive_utc()),
             updated_at: Some(Utc::now().naive_utc()),
+            tx_digest: "".to_string(),
+            event_seq: 0,
         }
     }
 }
@@ -106,11 +190,11 @@ impl From<&List> for lists::List {
 impl From<&Buy> for orders::Order {
     fn from(buy: &Buy) -> Self {
         orders::Order {
-            chain_id: 1,
-            coin_id: 1,
+            chain_id: Default::default(),
+            coin_id: Default::default(),
             token_id: buy.list_id.clone(),
             buyer_address: buy.buyer.clone(),
-            value: buy.ask.parse().unwrap(),
+            value: buy.ask,
             seller_address: buy.owner.clone(),
             order_type: OrderType::Sold,
             created_at: Some(Utc::now().naive_utc()),
@@ -118,6 +202,8 @@ impl From<&Buy> for orders::Order {
             list_id: buy.list_id.clone(),
             offer_id: None,
             sell_time: Default::default(),
+            tx_digest: "".to_string(),
+            event_seq: 0,
         }
     }
 }
@@ -125,20 +211,70 @@ impl From<&Buy> for orders::Order {
 impl From<&MakeOffer> for offers::Offer {
     fn from(make_offer: &MakeOffer) -> Self {
         offers::Offer {
-            chain_id: 1,
-            coin_id: 1,
+            chain_id: Default::default(),
+            coin_id: Default::default(),
             offer_id: make_offer.offer_id.clone(),
             list_id: make_offer.list_id.clone(),
             buyer_address: make_offer.owner.clone(),
             offer_type: OfferType::Listed,
-            offer_value: make_offer.offer_amount.parse().unwrap(),
+            offer_value: make_offer.offer_amount,
             expire_time: NaiveDateTime::from_timestamp_millis(
-                make_offer.expire_time.parse().unwrap(),
-            )
-            .unwrap(),
+                make_offer.expire_time,
+            ),
+            offer_time: Default::default(),
+            created_at: Some(Utc::now().naive_utc()),
+            updated_at: Some(Utc::now().naive_utc()),
+            tx_digest: "".to_string(),
+            event_seq: 0,
+            token_id: None,
+            collection_type: None,
+        }
+    }
+}
+
+impl From<&CollectionOfferWithType> for offers::Offer {
+    fn from(collection_offer: &CollectionOfferWithType) -> Self {
+        let offer = &collection_offer.offer;
+        offers::Offer {
+            chain_id: Default::default(),
+            coin_id: Default::default(),
+            offer_id: offer.offer_id.clone(),
+            // not tied to a listing.
+            list_id: "".to_string(),
+            buyer_address: offer.owner.clone(),
+            offer_type: OfferType::Listed,
+            offer_value: offer.offer_amount,
+            expire_time: NaiveDateTime::from_timestamp_millis(
+                offer.expire_time,
+            ),
             offer_time: Default::default(),
             created_at: Some(Utc::now().naive_utc()),
             updated_at: Some(Utc::now().naive_utc()),
+            tx_digest: "".to_string(),
+            event_seq: 0,
+            token_id: None,
+            collection_type: Some(collection_offer.collection_type.clone()),
+        }
+    }
+}
+
+impl From<&AcceptCollectionOffer> for orders::Order {
+    fn from(accept_offer: &AcceptCollectionOffer) -> Self {
+        orders::Order {
+            chain_id: Default::default(),
+            coin_id: Default::default(),
+            token_id: accept_offer.item_id.clone(),
+            buyer_address: accept_offer.buyer.clone(),
+            value: accept_offer.offer_amount,
+            seller_address: accept_offer.owner.clone(),
+            order_type: OrderType::Offer,
+            created_at: Some(Utc::now().naive_utc()),
+            updated_at: Some(Utc::now().naive_utc()),
+            list_id: "".to_string(),
+            offer_id: Some(accept_offer.offer_id.clone()),
+            sell_time: Default::default(),
+            tx_digest: "".to_string(),
+            event_seq: 0,
         }
     }
 }
@@ -146,11 +282,11 @@ impl From<&MakeOffer> for offers::Offer {
 impl From<&AcceptOffer> for orders::Order {
     fn from(accept_offer: &AcceptOffer) -> Self {
         orders::Order {
-            chain_id: 1,
-            coin_id: 1,
+            chain_id: Default::default(),
+            coin_id: Default::default(),
             token_id: accept_offer.list_id.clone(),
             buyer_address: accept_offer.buyer.clone(),
-            value: accept_offer.offer_amount.parse().unwrap(),
+            value: accept_offer.offer_amount,
             seller_address: accept_offer.owner.clone(),
             order_type: OrderType::Offer,
             created_at: Some(Utc::now().naive_utc()),
@@ -158,104 +294,386 @@ impl From<&AcceptOffer> for orders::Order {
             list_id: accept_offer.list_id.clone(),
             offer_id: Some(accept_offer.offer_id.clone()),
             sell_time: Default::default(),
+            tx_digest: "".to_string(),
+            event_seq: 0,
         }
     }
 }
 
-pub fn event_parse(e: &SuiEvent) -> Option<EventIndex> {
-    let event_data = e.parsed_json.clone();
+/// The payment coin `C` of `ListEvent<T, C>` and the other priced
+/// events, SUI for events that name none.
+fn coin_type(e: &SuiEvent) -> String {
+    match e.type_.type_params.get(1) {
+        Some(TypeTag::Struct(tag)) => tag.to_string(),
+        _ => SUI_COIN_TYPE.to_string(),
+    }
+}
+
+/// Parse an event of a BobYard package, only events declared in `module`
+/// are marketplace events.
+pub fn event_parse(e: &SuiEvent, module: &str) -> Result<Option<EventIndex>> {
+    if e.type_.module.as_str() != module {
+        return Ok(None);
+    }
     let event_name = e.type_.name.clone().to_string();
-    match event_name.as_str() {
-        "ListEvent" => {
-            let list: List = serde_json::from_value(event_data).unwrap();
-            Some(BobYardEvent::List(list).into())
+    let coin_type = coin_type(e);
+    let event = match event_name.as_str() {
+        "ListEvent" => BobYardEvent::List(List {
+            coin_type,
+            ..layout::decode(e)?
+        }),
+        "DeListEvent" => BobYardEvent::DeList(DeList {
+            coin_type,
+            ..layout::decode(e)?
+        }),
+        "BuyEvent" => BobYardEvent::Buy(Buy {
+            coin_type,
+            ..layout::decode(e)?
+        }),
+        "AcceptOfferEvent" => BobYardEvent::AcceptOffer(AcceptOffer {
+            coin_type,
+            ..layout::decode(e)?
+        }),
+        "OfferEvent" => BobYardEvent::MakeOffer(MakeOffer {
+            coin_type,
+            ..layout::decode(e)?
+        }),
+        "CancelOfferEvent" => BobYardEvent::CancelOffer(layout::decode(e)?),
+        "CollectionOfferEvent" => {
+            let collection_type = match e.type_.type_params.first() {
+                Some(TypeTag::Struct(tag)) => tag.to_string(),
+                _ => anyhow::bail!("CollectionOfferEvent without an NFT type"),
+            };
+            BobYardEvent::CollectionOffer(CollectionOfferWithType {
+                offer: CollectionOffer {
+                    coin_type,
+                    ..layout::decode(e)?
+                },
+                collection_type,
+            })
         }
-        "DeListEvent" => {
-            let de_list: DeList = serde_json::from_value(event_data).unwrap();
-            Some(BobYardEvent::DeList(de_list).into())
+        "CancelCollectionOfferEvent" => {
+            BobYardEvent::CancelCollectionOffer(layout::decode(e)?)
         }
-        "BuyEvent" => {
-            let buy: Buy = serde_json::from_value(event_data).unwrap();
-            Some(BobYardEvent::Buy(buy).into())
+        "AcceptCollectionOfferEvent" => {
+            BobYardEvent::AcceptCollectionOffer(AcceptCollectionOffer {
+                coin_type,
+                ..layout::decode(e)?
+            })
         }
-        "AcceptOfferEvent" => {
-            let accept_offer: AcceptOffer =
-                serde_json::from_value(event_data).unwrap();
-            Some(BobYardEvent::AcceptOffer(accept_offer).into())
+        _ => return Ok(None),
+    };
+    Ok(Some(event.into()))
+}
+
+/// Listing and offer an event closes, with the state each is closed as.
+fn closes(
+    e: &BobYardEvent,
+) -> (Option<(&str, ListType)>, Option<(&str, OfferType)>) {
+    match e {
+        BobYardEvent::DeList(de_list) => {
+            (Some((&de_list.list_id, ListType::Canceled)), None)
         }
-        "OfferEvent" => {
-            let make_offer: MakeOffer =
-                serde_json::from_value(event_data).unwrap();
-            Some(BobYardEvent::MakeOffer(make_offer).into())
+        BobYardEvent::Buy(buy) => (Some((&buy.list_id, ListType::Sold)), None),
+        BobYardEvent::AcceptOffer(accept_offer) => (
+            Some((&accept_offer.list_id, ListType::Sold)),
+            Some((&accept_offer.offer_id, OfferType::Sold)),
+        ),
+        BobYardEvent::CancelOffer(cancel_offer) => {
+            (None, Some((&cancel_offer.offer_id, OfferType::Canceled)))
         }
-        "CancelOfferEvent" => {
-            let cancel_offer: CancelOffer =
-                serde_json::from_value(event_data).unwrap();
-            Some(BobYardEvent::CancelOffer(cancel_offer).into())
+        BobYardEvent::CancelCollectionOffer(cancel_offer) => {
+            (None, Some((&cancel_offer.offer_id, OfferType::Canceled)))
         }
-        _ => None,
+        BobYardEvent::AcceptCollectionOffer(accept_offer) => {
+            (None, Some((&accept_offer.offer_id, OfferType::Sold)))
+        }
+        _ => (None, None),
     }
 }
 
 pub fn event_handle(
     e: &BobYardEvent,
+    id: &EventID,
+    chain_id: i64,
     event_time: i64,
     pg: &mut PgConnection,
 ) -> Result<()> {
-    //let _ = event.into_iter().for_each(|e| {
+    let time = NaiveDateTime::from_timestamp_millis(event_time).unwrap();
+    let tx_digest = id.tx_digest.to_string();
+    let (list, offer) = closes(e);
+    if let Some((list_id, list_type)) = list {
+        lists::close_by_event(
+            pg,
+            chain_id,
+            list_id,
+            list_type,
+            &tx_digest,
+            id.event_seq as i64,
+            time,
+        )?;
+    }
+    if let Some((offer_id, offer_type)) = offer {
+        offers::close_by_event(
+            pg,
+            chain_id,
+            offer_id,
+            offer_type,
+            &tx_digest,
+            id.event_seq as i64,
+            time,
+        )?;
+    }
+
     match e {
-        BobYardEvent::List(list) => {
-            let mut list: lists::List = list.into();
-            list.list_time =
-                NaiveDateTime::from_timestamp_millis(event_time as i64)
-                    .unwrap();
+        BobYardEvent::List(list_event) => {
+            let mut list: lists::List = list_event.into();
+            list.list_time = time;
+            list.chain_id = chain_id;
+            list.coin_id =
+                coins::resolve_id(pg, &list_event.coin_type, event_time)?;
+            list.tx_digest = tx_digest;
+            list.event_seq = id.event_seq as i64;
             info!("list {:?}", list);
-            lists::batch_insert(pg, &vec![list]).expect("batch_insert error");
+            lists::batch_insert(pg, &vec![list])?;
         }
         BobYardEvent::DeList(de_list) => {
             info!("de_list {:?}", de_list);
-            lists::delete(pg, &de_list.list_id).expect("batch_insert error");
         }
         BobYardEvent::Buy(buy) => {
-            // delete the list.
-            lists::delete(pg, &buy.list_id).expect("batch_insert error");
-            // insert the order.
             let mut order: orders::Order = buy.into();
             info!("buy {:?}", order);
-            order.sell_time =
-                NaiveDateTime::from_timestamp_millis(event_time as i64)
-                    .unwrap();
-            orders::batch_insert(pg, &vec![order]).expect("batch_insert error");
+            order.sell_time = time;
+            order.chain_id = chain_id;
+            order.coin_id = coins::resolve_id(pg, &buy.coin_type, event_time)?;
+            order.tx_digest = tx_digest;
+            order.event_seq = id.event_seq as i64;
+            orders::batch_insert(pg, &vec![order])?;
         }
         BobYardEvent::AcceptOffer(accept_offer) => {
-            // delete the list.
-            lists::delete(pg, &accept_offer.list_id)
-                .expect("batch_insert error");
-            offers::delete(pg, &accept_offer.offer_id)
-                .expect("batch_insert error");
             let mut order: orders::Order = accept_offer.into();
             info!("accept_offer {:?}", order);
-            order.sell_time =
-                NaiveDateTime::from_timestamp_millis(event_time as i64)
-                    .unwrap();
-            orders::batch_insert(pg, &vec![order]).expect("batch_insert error");
+            order.sell_time = time;
+            order.chain_id = chain_id;
+            order.coin_id =
+                coins::resolve_id(pg, &accept_offer.coin_type, event_time)?;
+            order.tx_digest = tx_digest;
+            order.event_seq = id.event_seq as i64;
+            orders::batch_insert(pg, &vec![order])?;
         }
         BobYardEvent::MakeOffer(make_offer) => {
             let mut offer_to_db: offers::Offer = make_offer.into();
-            offer_to_db.offer_time =
-                NaiveDateTime::from_timestamp_millis(event_time as i64)
-                    .unwrap();
+            offer_to_db.offer_time = time;
+            offer_to_db.chain_id = chain_id;
+            offer_to_db.coin_id =
+                coins::resolve_id(pg, &make_offer.coin_type, event_time)?;
+            offer_to_db.tx_digest = tx_digest;
+            offer_to_db.event_seq = id.event_seq as i64;
+            offer_to_db.token_id =
+                lists::query_token(pg, chain_id, &make_offer.list_id)?;
+            if let Some(token_id) = &offer_to_db.token_id {
+                offer_to_db.collection_type =
+                    tokens::query_token(pg, chain_id, token_id)?
+                        .map(|token| token.collection_type);
+            }
             info!("offer_to_db {:?}", offer_to_db);
-            offers::batch_insert(pg, &vec![offer_to_db])
-                .expect("batch_insert error");
+            offers::batch_insert(pg, &vec![offer_to_db])?;
         }
         BobYardEvent::CancelOffer(cancel_offer) => {
             info!("cancel_offer {:?}", cancel_offer);
-            offers::delete(pg, &cancel_offer.offer_id)
-                .expect("batch_insert error");
+        }
+        BobYardEvent::CollectionOffer(collection_offer) => {
+            let mut offer_to_db: offers::Offer = collection_offer.into();
+            offer_to_db.offer_time = time;
+            offer_to_db.chain_id = chain_id;
+            offer_to_db.coin_id = coins::resolve_id(
+                pg,
+                &collection_offer.offer.coin_type,
+                event_time,
+            )?;
+            offer_to_db.tx_digest = tx_digest;
+            offer_to_db.event_seq = id.event_seq as i64;
+            info!("collection_offer {:?}", offer_to_db);
+            offers::batch_insert(pg, &vec![offer_to_db])?;
+        }
+        BobYardEvent::CancelCollectionOffer(cancel_offer) => {
+            info!("cancel_collection_offer {:?}", cancel_offer);
+        }
+        BobYardEvent::AcceptCollectionOffer(accept_offer) => {
+            let mut order: orders::Order = accept_offer.into();
+            info!("accept_collection_offer {:?}", order);
+            order.sell_time = time;
+            order.chain_id = chain_id;
+            order.coin_id =
+                coins::resolve_id(pg, &accept_offer.coin_type, event_time)?;
+            order.tx_digest = tx_digest;
+            order.event_seq = id.event_seq as i64;
+            orders::batch_insert(pg, &vec![order])?;
         }
     }
-    //});
 
     Ok(())
 }
+
+/// Offer made and canceled activities for token offers and collection-wide
+/// bids. Run after `event_handle` so canceled offers are in `offers`.
+pub fn offer_activities(
+    events: &Vec<(EventID, EventIndex)>,
+    collection_types: &HashMap<String, String>,
+    chain_id: i64,
+    checkpoint: i64,
+    event_time: i64,
+    pg: &mut PgConnection,
+) -> Result<Vec<Activity>> {
+    let time = NaiveDateTime::from_timestamp_millis(event_time).unwrap();
+    let collection_id = |collection_type: &str| {
+        collection_types
+            .get(collection_type)
+            .cloned()
+            .unwrap_or_else(|| collection_type.to_string())
+    };
+
+    let mut activities = vec![];
+    for (id, e) in events {
+        let e = match e {
+            EventIndex::BobYard(e) => e,
+            _ => continue,
+        };
+        let (
+            transfer_type,
+            owner,
+            amount,
+            coin_type,
+            token_id,
+            collection_type,
+        ) = match e {
+            BobYardEvent::MakeOffer(offer) => (
+                ActivityType::OfferMade,
+                offer.owner.clone(),
+                offer.offer_amount,
+                Some(offer.coin_type.clone()),
+                lists::query_token(pg, chain_id, &offer.list_id)?,
+                None,
+            ),
+            BobYardEvent::CollectionOffer(offer) => (
+                ActivityType::OfferMade,
+                offer.offer.owner.clone(),
+                offer.offer.offer_amount,
+                Some(offer.offer.coin_type.clone()),
+                None,
+                Some(offer.collection_type.clone()),
+            ),
+            BobYardEvent::CancelOffer(CancelOffer { offer_id, .. })
+            | BobYardEvent::CancelCollectionOffer(CancelCollectionOffer {
+                offer_id,
+                ..
+            }) => match offers::query_offer(pg, chain_id, offer_id)? {
+                Some(offer) => (
+                    ActivityType::OfferCanceled,
+                    offer.buyer_address,
+                    offer.offer_value,
+                    coins::query_type(pg, offer.coin_id)?,
+                    offer.token_id,
+                    offer.collection_type,
+                ),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        let token = match &token_id {
+            Some(token_id) => tokens::query_token(pg, chain_id, token_id)?,
+            None => None,
+        };
+        let collection_id = match (&token, &collection_type) {
+            (Some(token), _) => token.collection_id.clone(),
+            (None, Some(collection_type)) => collection_id(collection_type),
+            (None, None) => "".to_string(),
+        };
+        let (token_id, name, collection_name) = match token {
+            Some(token) => {
+                (token.token_id, token.token_name, token.collection_name)
+            }
+            None => {
+                (token_id.unwrap_or_default(), "".to_string(), "".to_string())
+            }
+        };
+        activities.push(Activity {
+            chain_id,
+            // offers change no object, they are keyed by checkpoint and
+            // their event.
+            version: checkpoint,
+            tx: id.tx_digest.to_string(),
+            event_account_address: owner.clone(),
+            event_creation_number: 0,
+            event_sequence_number: id.event_seq as i64,
+            collection_data_id_hash: collection_id,
+            token_data_id_hash: token_id,
+            property_version: checkpoint,
+            creator_address: "".to_string(),
+            collection_name,
+            name,
+            transfer_type,
+            from_address: Some(owner),
+            to_address: None,
+            token_amount: amount,
+            coin_type,
+            coin_amount: Amount(0),
+            transaction_timestamp: time,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+        });
+    }
+    Ok(activities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buy_closes_listing_as_sold() {
+        let buy = BobYardEvent::Buy(Buy {
+            list_id: "0x1".to_string(),
+            item_id: "0x2".to_string(),
+            ask: Amount::default(),
+            coin_type: SUI_COIN_TYPE.to_string(),
+            owner: "0x3".to_string(),
+            buyer: "0x4".to_string(),
+        });
+        assert_eq!(closes(&buy), (Some(("0x1", ListType::Sold)), None));
+    }
+
+    #[test]
+    fn de_list_closes_listing_as_canceled() {
+        let de_list = BobYardEvent::DeList(DeList {
+            list_id: "0x1".to_string(),
+            list_item_id: "0x2".to_string(),
+            expire_time: 0,
+            ask: Amount::default(),
+            coin_type: SUI_COIN_TYPE.to_string(),
+            owner: "0x3".to_string(),
+        });
+        assert_eq!(closes(&de_list), (Some(("0x1", ListType::Canceled)), None));
+    }
+
+    #[test]
+    fn accept_offer_closes_listing_and_offer_as_sold() {
+        let accept = BobYardEvent::AcceptOffer(AcceptOffer {
+            offer_id: "0x5".to_string(),
+            list_id: "0x1".to_string(),
+            item_id: "0x2".to_string(),
+            offer_amount: Amount::default(),
+            coin_type: SUI_COIN_TYPE.to_string(),
+            owner: "0x3".to_string(),
+            buyer: "0x4".to_string(),
+        });
+        assert_eq!(
+            closes(&accept),
+            (
+                Some(("0x1", ListType::Sold)),
+                Some(("0x5", OfferType::Sold))
+            )
+        );
+    }
+}
@@ -2,17 +2,27 @@ use anyhow::Result;
 use chrono::{NaiveDateTime, Utc};
 use diesel::PgConnection;
 
+use crate::models::activities::{Activity, ActivityType};
+use crate::models::amount::Amount;
+use crate::models::coins::{SUI_COIN_ID, SUI_COIN_TYPE};
+use crate::models::kiosks;
 use crate::models::lists::{self, ListType, MarketType};
+use crate::models::orders::{self, OrderType};
+use crate::models::tokens::Token;
+use crate::ObjectStatus;
 use serde::{Deserialize, Serialize};
 use sui_sdk::rpc_types::SuiEvent;
+use sui_sdk::types::event::EventID;
 use tracing::info;
 
-use super::EventIndex;
+use super::{layout, EventIndex};
 
+/// Kiosk sales are always paid in SUI.
 #[derive(Debug)]
 pub enum KioskEvent {
     ItemListed(ItemListedWithSender),
     ItemDelisted(ItemDelisted),
+    ItemPurchased(ItemPurchasedWithSender),
 }
 
 impl From<KioskEvent> for EventIndex {
@@ -21,16 +31,18 @@ impl From<KioskEvent> for EventIndex {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ItemListed {
-    id: String,
+    #[serde(deserialize_with = "layout::id")]
     kiosk: String,
-    price: String,
+    #[serde(deserialize_with = "layout::id")]
+    id: String,
+    price: Amount,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ItemListedWithSender {
     id: String,
     kiosk: String,
-    price: String,
+    price: Amount,
     sender: String,
 }
 
@@ -48,70 +60,204 @@ impl ItemListedWithSender {
 impl From<(&ItemListedWithSender)> for lists::List {
     fn from(list: &ItemListedWithSender) -> Self {
         lists::List {
-            chain_id: 1,
-            coin_id: 1,
-            list_id: list.kiosk.clone(),
+            chain_id: Default::default(),
+            coin_id: SUI_COIN_ID,
+            list_id: kiosk_list_id(&list.kiosk, &list.id),
             list_time: Utc::now().naive_utc(),
             token_id: list.id.clone(),
             seller_address: list.sender.clone(),
-            seller_value: list.price.parse().unwrap(),
+            seller_value: list.price,
             list_type: ListType::Listed,
             market_type: MarketType::Kiosk,
             expire_time: None,
             created_at: Some(Utc::now().naive_utc()),
             updated_at: Some(Utc::now().naive_utc()),
+            tx_digest: "".to_string(),
+            event_seq: 0,
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ItemDelisted {
+    #[serde(deserialize_with = "layout::id")]
+    kiosk: String,
+    #[serde(deserialize_with = "layout::id")]
     id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ItemPurchased {
+    #[serde(deserialize_with = "layout::id")]
     kiosk: String,
+    #[serde(deserialize_with = "layout::id")]
+    id: String,
+    price: Amount,
 }
 
-pub fn event_parse(e: &SuiEvent) -> Option<super::EventIndex> {
-    let event_data = e.parsed_json.clone();
-    let event_module = e.type_.module.to_string();
+#[derive(Debug)]
+pub struct ItemPurchasedWithSender {
+    pub id: String,
+    pub kiosk: String,
+    pub price: Amount,
+    pub buyer: String,
+}
+
+/// A kiosk lists each item at most once at a time.
+pub fn kiosk_list_id(kiosk: &str, item: &str) -> String {
+    format!("{}:{}", kiosk, item)
+}
 
-    let event_name = e.type_.name.clone().to_string();
+/// The holder of the kiosk's owner cap, else the seller of its latest
+/// listing of the item, else `fallback`.
+fn kiosk_seller(
+    pg: &mut PgConnection,
+    chain_id: i64,
+    kiosk: &str,
+    item: &str,
+    fallback: &str,
+) -> Result<String> {
+    if let Some(owner) = kiosks::query_owner(pg, chain_id, kiosk)? {
+        return Ok(owner);
+    }
+    let seller =
+        lists::query_seller(pg, chain_id, &kiosk_list_id(kiosk, item))?;
+    Ok(seller.unwrap_or_else(|| fallback.to_string()))
+}
 
-    match event_name.as_str() {
-        "ItemListed" => {
-            let list: ItemListed = serde_json::from_value(event_data).unwrap();
+pub fn event_parse(e: &SuiEvent) -> Result<Option<super::EventIndex>> {
+    match (e.type_.module.as_str(), e.type_.name.as_str()) {
+        ("kiosk", "ItemListed") => {
+            let list: ItemListed = layout::decode(e)?;
             let with_sender =
                 ItemListedWithSender::new(list, e.sender.to_string());
-            Some(KioskEvent::ItemListed(with_sender).into())
+            Ok(Some(KioskEvent::ItemListed(with_sender).into()))
         }
-        "ItemDelisted" => {
-            let de_list: ItemDelisted =
-                serde_json::from_value(event_data).unwrap();
-            Some(KioskEvent::ItemDelisted(de_list).into())
+        ("kiosk", "ItemDelisted") => {
+            let de_list: ItemDelisted = layout::decode(e)?;
+            Ok(Some(KioskEvent::ItemDelisted(de_list).into()))
         }
-        _ => None,
+        ("kiosk", "ItemPurchased") => {
+            let purchase: ItemPurchased = layout::decode(e)?;
+            Ok(Some(
+                KioskEvent::ItemPurchased(ItemPurchasedWithSender {
+                    id: purchase.id,
+                    kiosk: purchase.kiosk,
+                    price: purchase.price,
+                    buyer: e.sender.to_string(),
+                })
+                .into(),
+            ))
+        }
+        _ => Ok(None),
     }
 }
 
 pub fn event_handle(
     e: &KioskEvent,
+    id: &EventID,
+    chain_id: i64,
     event_time: i64,
     pg: &mut PgConnection,
 ) -> Result<()> {
     match e {
-        KioskEvent::ItemListed(list) => {
-            let mut list: lists::List = list.into();
+        KioskEvent::ItemListed(listed) => {
+            let mut list: lists::List = listed.into();
             list.list_time =
                 NaiveDateTime::from_timestamp_millis(event_time as i64)
                     .unwrap();
+            list.chain_id = chain_id;
+            list.tx_digest = id.tx_digest.to_string();
+            list.event_seq = id.event_seq as i64;
+            if let Some(owner) =
+                kiosks::query_owner(pg, chain_id, &listed.kiosk)?
+            {
+                list.seller_address = owner;
+            }
 
             info!("list {:?}", list);
-            lists::batch_insert(pg, &vec![list]).expect("batch_insert error");
+            lists::batch_insert(pg, &vec![list])?;
         }
         KioskEvent::ItemDelisted(de_list) => {
             info!("de_list {:?}", de_list);
-            lists::delete(pg, &de_list.id).expect("batch_insert error");
+            lists::close(
+                pg,
+                chain_id,
+                &kiosk_list_id(&de_list.kiosk, &de_list.id),
+                ListType::Canceled,
+            )?;
+        }
+        KioskEvent::ItemPurchased(purchase) => {
+            let list_id = kiosk_list_id(&purchase.kiosk, &purchase.id);
+            let seller = kiosk_seller(
+                pg,
+                chain_id,
+                &purchase.kiosk,
+                &purchase.id,
+                &purchase.kiosk,
+            )?;
+            lists::close(pg, chain_id, &list_id, ListType::Sold)?;
+
+            let order = orders::Order {
+                chain_id,
+                coin_id: SUI_COIN_ID,
+                list_id,
+                token_id: purchase.id.clone(),
+                offer_id: None,
+                seller_address: seller,
+                buyer_address: purchase.buyer.clone(),
+                order_type: OrderType::Sold,
+                value: purchase.price,
+                sell_time: NaiveDateTime::from_timestamp_millis(event_time)
+                    .unwrap(),
+                created_at: Some(Utc::now().naive_utc()),
+                updated_at: Some(Utc::now().naive_utc()),
+                tx_digest: id.tx_digest.to_string(),
+                event_seq: id.event_seq as i64,
+            };
+            info!("purchase {:?}", order);
+            orders::batch_insert(pg, &vec![order])?;
         }
     }
 
     Ok(())
 }
+
+/// Sold activities for kiosk purchases of tokens indexed in this
+/// checkpoint.
+pub fn purchase_activities(
+    events: &Vec<(EventID, EventIndex)>,
+    tokens: &Vec<(ObjectStatus, (Token, String))>,
+    chain_id: i64,
+    pg: &mut PgConnection,
+) -> Result<Vec<Activity>> {
+    let mut activities = vec![];
+    for (_, e) in events {
+        let purchase = match e {
+            EventIndex::KioskEvent(KioskEvent::ItemPurchased(purchase)) => {
+                purchase
+            }
+            _ => continue,
+        };
+        let token =
+            match tokens.iter().find(|(_, t)| t.0.token_id == purchase.id) {
+                Some((_, token)) => token,
+                None => continue,
+            };
+
+        let mut sold =
+            Activity::new_from_token_with_type(ActivityType::Sold, token);
+        sold.from_address = Some(kiosk_seller(
+            pg,
+            chain_id,
+            &purchase.kiosk,
+            &purchase.id,
+            &purchase.kiosk,
+        )?);
+        sold.to_address = Some(purchase.buyer.clone());
+        sold.token_amount = purchase.price;
+        sold.coin_type = Some(SUI_COIN_TYPE.to_string());
+        activities.push(sold);
+    }
+    Ok(activities)
+}
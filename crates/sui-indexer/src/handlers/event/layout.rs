@@ -0,0 +1,159 @@
+//! Decoding of event BCS into the event structs.
+//!
+//! An event struct declares the layout of its Move struct: its fields are
+//! listed in Move declaration order, and fields whose Rust type differs from
+//! the Move one name their Move type with the helpers below.
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer};
+use sui_sdk::rpc_types::SuiEvent;
+use sui_sdk::types::base_types::{ObjectID, SuiAddress};
+
+/// Decode the contents of `e` into `T`. Every byte has to be used, so a
+/// layout that drifted from the Move struct fails instead of misreading
+/// fields.
+pub fn decode<T: DeserializeOwned>(e: &SuiEvent) -> Result<T> {
+    bcs::from_bytes(&e.bcs).map_err(|err| {
+        anyhow!(
+            "Failed to decode {} as {}: {}",
+            e.type_,
+            std::any::type_name::<T>(),
+            err
+        )
+    })
+}
+
+/// A Move `ID`, kept in its `0x` prefixed form.
+pub fn id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    ObjectID::deserialize(deserializer).map(|id| id.to_string())
+}
+
+/// A Move `address`.
+pub fn address<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    SuiAddress::deserialize(deserializer).map(|address| address.to_string())
+}
+
+/// A `u64` of milliseconds since the epoch, rejecting values that are not
+/// representable as a `NaiveDateTime`.
+pub fn timestamp_ms<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<i64, D::Error> {
+    let millis = u64::deserialize(deserializer)?;
+    i64::try_from(millis)
+        .ok()
+        .filter(|millis| {
+            NaiveDateTime::from_timestamp_millis(*millis).is_some()
+        })
+        .ok_or_else(|| {
+            D::Error::custom(format!("timestamp {} is out of range", millis))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::event::bobyard_event::List;
+    use crate::handlers::event::kiosk_event::ItemListed;
+    use crate::handlers::event::origin_byte_event::{
+        MintCollection, TradeFilled,
+    };
+    use crate::models::amount::Amount;
+    use serde::Serialize;
+
+    fn object(hex: &str) -> ObjectID {
+        ObjectID::from_hex_literal(hex).unwrap()
+    }
+
+    fn account(hex: &str) -> SuiAddress { SuiAddress::from(object(hex)) }
+
+    /// `decode` without the `SuiEvent` around the bytes.
+    fn from_bcs<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        Ok(bcs::from_bytes(bytes)?)
+    }
+
+    /// Decoding fails once a byte is missing or one is left over.
+    fn assert_exact<T: DeserializeOwned>(bytes: &[u8]) {
+        assert!(from_bcs::<T>(bytes).is_ok());
+        assert!(from_bcs::<T>(&bytes[..bytes.len() - 1]).is_err());
+        let mut extended = bytes.to_vec();
+        extended.push(0);
+        assert!(from_bcs::<T>(&extended).is_err());
+    }
+
+    fn bytes<T: Serialize>(fields: &T) -> Vec<u8> {
+        bcs::to_bytes(fields).unwrap()
+    }
+
+    #[test]
+    fn decodes_bobyard_list() {
+        let fixture = bytes(&(
+            object("0x1"),
+            object("0x2"),
+            1_700_000_000_000u64,
+            u64::MAX,
+            account("0xa"),
+        ));
+        let list: List = from_bcs(&fixture).unwrap();
+        assert_eq!(list.list_id, object("0x1").to_string());
+        assert_eq!(list.list_item_id, object("0x2").to_string());
+        assert_eq!(list.expire_time, 1_700_000_000_000);
+        assert_eq!(list.ask, Amount(u64::MAX));
+        assert_eq!(list.owner, account("0xa").to_string());
+        assert_exact::<List>(&fixture);
+    }
+
+    #[test]
+    fn rejects_out_of_range_timestamps() {
+        let fixture = bytes(&(
+            object("0x1"),
+            object("0x2"),
+            u64::MAX,
+            1u64,
+            account("0xa"),
+        ));
+        assert!(from_bcs::<List>(&fixture).is_err());
+    }
+
+    #[test]
+    fn decodes_origin_byte_trade() {
+        let fixture = bytes(&(
+            object("0x1"),
+            object("0x2"),
+            account("0xb"),
+            object("0x3"),
+            account("0xc"),
+            object("0x4"),
+            42u64,
+        ));
+        let trade: TradeFilled = from_bcs(&fixture).unwrap();
+        assert_eq!(trade.orderbook, object("0x1").to_string());
+        assert_eq!(trade.nft, object("0x2").to_string());
+        assert_eq!(trade.buyer, account("0xb").to_string());
+        assert_eq!(trade.buyer_kiosk, object("0x3").to_string());
+        assert_eq!(trade.seller, account("0xc").to_string());
+        assert_eq!(trade.seller_kiosk, object("0x4").to_string());
+        assert_eq!(trade.price, Amount(42));
+        assert_exact::<TradeFilled>(&fixture);
+    }
+
+    #[test]
+    fn decodes_origin_byte_mint_collection() {
+        let fixture = bytes(&(object("0x1"), "5::nft::Nft".to_string()));
+        let mint: MintCollection = from_bcs(&fixture).unwrap();
+        assert_eq!(mint.collection_id, object("0x1").to_string());
+        assert_eq!(mint.type_name.name, "5::nft::Nft");
+        assert_exact::<MintCollection>(&fixture);
+    }
+
+    #[test]
+    fn decodes_kiosk_item_listed() {
+        let fixture = bytes(&(object("0x1"), object("0x2"), 7u64));
+        assert_exact::<ItemListed>(&fixture);
+    }
+}
@@ -1,13 +1,30 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use diesel::PgConnection;
-use sui_sdk::rpc_types::SuiEvent;
+use std::collections::HashMap;
+use sui_sdk::rpc_types::{Checkpoint, SuiEvent, SuiObjectData, SuiParsedData};
+use sui_sdk::types::base_types::ObjectID;
+use sui_sdk::types::event::EventID;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::handlers::{
+    failed_event, CheckpointHandler, CheckpointState, HandlerContext,
+};
+use crate::indexer::CheckpointData;
+use crate::models::failed_items::FailedItem;
+use crate::models::marketplace_packages::{
+    self, Marketplace, MarketplacePackage,
+};
+use crate::ObjectStatus;
 
 pub mod bobyard_event;
 pub mod kiosk_event;
+pub mod layout;
 pub mod origin_byte_event;
 
 const SYSTEM_MODULE: &str =
     "0x0000000000000000000000000000000000000000000000000000000000000002";
+const UPGRADE_CAP: &str = "0x2::package::UpgradeCap";
 
 #[derive(Debug)]
 pub enum EventIndex {
@@ -16,63 +33,296 @@ pub enum EventIndex {
     KioskEvent(kiosk_event::KioskEvent),
 }
 
+/// The parser an event goes to.
+enum Route {
+    Marketplace(Marketplace),
+    Kiosk,
+}
+
+/// Every known package version of the indexed marketplaces.
 pub struct EventAccount {
-    bob_yard: String,
-    origin_byte: String,
+    /// Package id to the marketplace it is a version of.
+    packages: HashMap<String, Marketplace>,
+    /// `UpgradeCap` id to the marketplace it upgrades.
+    upgrade_caps: HashMap<String, Marketplace>,
+    /// Module declaring the BobYard events.
+    bob_yard_module: String,
+}
+
+fn normalize_id(id: &str) -> Result<String> {
+    ObjectID::from_hex_literal(id)
+        .map(|id| id.to_string())
+        .map_err(|e| anyhow!("Invalid object id {}: {}", id, e))
 }
 
 impl EventAccount {
-    pub fn new(bob_yard: String, origin_byte: String) -> Self {
-        Self {
-            bob_yard,
-            origin_byte,
+    pub fn new(config: &Config) -> Result<Self> {
+        let mut account = Self {
+            packages: HashMap::new(),
+            upgrade_caps: HashMap::new(),
+            bob_yard_module: config.bob_yard_module.clone(),
+        };
+        for (marketplace, packages, upgrade_cap) in [
+            (
+                Marketplace::BobYard,
+                &config.bob_yard,
+                &config.bob_yard_upgrade_cap,
+            ),
+            (
+                Marketplace::OriginByte,
+                &config.origin_byte,
+                &config.origin_byte_upgrade_cap,
+            ),
+            (
+                Marketplace::OriginByteOrderbook,
+                &config.origin_byte_orderbook,
+                &config.origin_byte_orderbook_upgrade_cap,
+            ),
+        ] {
+            for package in packages {
+                account.packages.insert(normalize_id(package)?, marketplace);
+            }
+            if let Some(upgrade_cap) = upgrade_cap {
+                account
+                    .upgrade_caps
+                    .insert(normalize_id(upgrade_cap)?, marketplace);
+            }
         }
+        Ok(account)
+    }
+
+    /// Add the package versions discovered by earlier runs.
+    pub fn extend(&mut self, packages: Vec<MarketplacePackage>) {
+        for package in packages {
+            if let Some(upgrade_cap) = package.upgrade_cap_id {
+                self.upgrade_caps.insert(upgrade_cap, package.marketplace);
+            }
+            self.packages
+                .insert(package.package_id, package.marketplace);
+        }
+    }
+
+    /// Events are routed on their type rather than on the called package,
+    /// types keep the id of the package version that declared them. The
+    /// parsers then match the module and name of the type.
+    fn route(&self, e: &SuiEvent) -> Option<Route> {
+        let type_package = ObjectID::from(e.type_.address).to_string();
+        if type_package == SYSTEM_MODULE {
+            return match e.type_.module.as_str() {
+                "kiosk" => Some(Route::Kiosk),
+                _ => None,
+            };
+        }
+        self.packages
+            .get(&type_package)
+            .map(|marketplace| Route::Marketplace(*marketplace))
+    }
+
+    /// Follow the `UpgradeCap`s of the marketplaces. An upgrade made with a
+    /// known cap adds the new package version, and a cap pointing at a
+    /// known package becomes known. Returns what was learned.
+    fn discover(
+        &mut self,
+        object_changes: &Vec<(ObjectStatus, SuiObjectData, String, u64)>,
+        chain_id: i64,
+    ) -> Vec<MarketplacePackage> {
+        let mut discovered = vec![];
+        for (status, obj, _, timestamp) in object_changes {
+            if *status != ObjectStatus::Created
+                && *status != ObjectStatus::Mutated
+            {
+                continue;
+            }
+            if obj.type_.as_ref().map(|t| t.to_string()).as_deref()
+                != Some(UPGRADE_CAP)
+            {
+                continue;
+            }
+            let fields = match obj.content.as_ref() {
+                Some(SuiParsedData::MoveObject(parse_obj)) => {
+                    parse_obj.fields.clone().to_json_value()
+                }
+                _ => continue,
+            };
+            let package_id = match fields["package"]
+                .as_str()
+                .and_then(|id| normalize_id(id).ok())
+            {
+                Some(package_id) => package_id,
+                None => continue,
+            };
+            let cap_id = obj.object_id.to_string();
+            let marketplace = match self
+                .upgrade_caps
+                .get(&cap_id)
+                .or_else(|| self.packages.get(&package_id))
+            {
+                Some(marketplace) => *marketplace,
+                None => continue,
+            };
+            if self.packages.contains_key(&package_id)
+                && self.upgrade_caps.contains_key(&cap_id)
+            {
+                continue;
+            }
+
+            info!(
+                ?marketplace,
+                package_id, cap_id, "Marketplace package discovered"
+            );
+            self.packages.insert(package_id.clone(), marketplace);
+            self.upgrade_caps.insert(cap_id.clone(), marketplace);
+            discovered.push(MarketplacePackage {
+                chain_id,
+                package_id,
+                marketplace,
+                upgrade_cap_id: Some(cap_id),
+                version: fields["version"]
+                    .as_str()
+                    .and_then(|v| v.parse().ok())
+                    .or_else(|| fields["version"].as_i64())
+                    .unwrap_or_default(),
+                created_at: *timestamp as i64,
+            });
+        }
+        discovered
     }
 }
 
+/// Parse the marketplace events of `checkpoint`. Events that fail to
+/// decode are skipped and added to `failures`.
 pub fn parse_event(
+    checkpoint: &Checkpoint,
     events: &Vec<SuiEvent>,
     event_account: &EventAccount,
-) -> Result<Vec<EventIndex>> {
+    chain_id: i64,
+    failures: &mut Vec<FailedItem>,
+) -> Result<Vec<(EventID, EventIndex)>> {
     let events = events
         .into_iter()
         .filter_map(|e| {
-            dbg!(&e.package_id.to_string());
-            dbg!(&e.parsed_json);
-            if e.package_id.to_string() == event_account.bob_yard {
-                bobyard_event::event_parse(e)
-            } else if e.package_id.to_string() == event_account.origin_byte {
-                origin_byte_event::event_parse(e)
-            } else if &e.package_id.to_string() == SYSTEM_MODULE {
-                kiosk_event::event_parse(e)
-            } else {
-                None
+            let event = match event_account.route(e) {
+                Some(Route::Marketplace(Marketplace::BobYard)) => {
+                    bobyard_event::event_parse(
+                        e,
+                        &event_account.bob_yard_module,
+                    )
+                }
+                Some(Route::Marketplace(
+                    marketplace @ (Marketplace::OriginByte
+                    | Marketplace::OriginByteOrderbook),
+                )) => origin_byte_event::event_parse(e, marketplace),
+                Some(Route::Kiosk) => kiosk_event::event_parse(e),
+                None => Ok(None),
+            };
+            match event {
+                Ok(event) => event.map(|event| (e.id.clone(), event)),
+                Err(err) => {
+                    warn!(
+                        tx_digest = %e.id.tx_digest,
+                        event_seq = e.id.event_seq,
+                        "Failed to parse event: {:#}", err
+                    );
+                    failures.push(failed_event(chain_id, checkpoint, e, &err));
+                    None
+                }
             }
         })
-        .collect::<Vec<EventIndex>>();
+        .collect::<Vec<(EventID, EventIndex)>>();
 
     Ok(events)
 }
 
 pub fn event_handle(
-    event: &Vec<EventIndex>,
+    event: &Vec<(EventID, EventIndex)>,
+    chain_id: i64,
     event_time: i64,
     pg: &mut PgConnection,
 ) -> Result<()> {
-    for e in event {
+    for (id, e) in event {
         match e {
             EventIndex::BobYard(e) => {
-                bobyard_event::event_handle(e, event_time, pg)?;
+                bobyard_event::event_handle(e, id, chain_id, event_time, pg)?;
             }
             EventIndex::OriginByte(e) => {
-                origin_byte_event::event_handle(e, event_time, pg)?;
+                origin_byte_event::event_handle(
+                    e, id, chain_id, event_time, pg,
+                )?;
             }
             EventIndex::KioskEvent(e) => {
-                dbg!("kiosk event: {:?}", e);
-                kiosk_event::event_handle(e, event_time, pg)?;
+                kiosk_event::event_handle(e, id, chain_id, event_time, pg)?;
             }
         }
     }
 
     Ok(())
 }
+
+/// Parses marketplace events and applies them to `lists`, `offers` and
+/// `orders`.
+pub struct MarketplaceHandler {
+    event_account: EventAccount,
+}
+
+impl MarketplaceHandler {
+    pub fn new(event_account: EventAccount) -> Self { Self { event_account } }
+}
+
+impl CheckpointHandler for MarketplaceHandler {
+    fn name(&self) -> &str { "marketplace" }
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        (checkpoint, _, object_changed, events): &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let discovered = self
+            .event_account
+            .discover(object_changed, ctx.network.chain_id());
+        if discovered.len() > 0 {
+            marketplace_packages::batch_insert(conn, &discovered)?;
+        }
+
+        let events = parse_event(
+            checkpoint,
+            events,
+            &self.event_account,
+            ctx.network.chain_id(),
+            &mut state.failures,
+        )?;
+        if events.len() > 0 {
+            event_handle(
+                &events,
+                ctx.network.chain_id(),
+                checkpoint.timestamp_ms as i64,
+                conn,
+            )?;
+            state.activities.extend(kiosk_event::purchase_activities(
+                &events,
+                &state.tokens,
+                ctx.network.chain_id(),
+                conn,
+            )?);
+            state.activities.extend(bobyard_event::offer_activities(
+                &events,
+                &ctx.collection_types,
+                ctx.network.chain_id(),
+                checkpoint.sequence_number as i64,
+                checkpoint.timestamp_ms as i64,
+                conn,
+            )?);
+            state.activities.extend(origin_byte_event::mint_activities(
+                &events,
+                object_changed,
+                &ctx.collection_types,
+                ctx.network.chain_id(),
+                checkpoint.timestamp_ms as i64,
+            ));
+        }
+
+        state.events = events;
+        Ok(())
+    }
+}
@@ -1,24 +1,472 @@
 use anyhow::Result;
+use chrono::{NaiveDateTime, Utc};
 use diesel::PgConnection;
-use sui_sdk::rpc_types::SuiEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use sui_sdk::rpc_types::{SuiEvent, SuiObjectData};
+use sui_sdk::types::event::EventID;
+use sui_sdk::types::TypeTag;
+use tracing::info;
 
+use super::{layout, EventIndex};
+use crate::handlers::transfer_policy::normalize_type;
+use crate::models::activities::{Activity, ActivityType};
+use crate::models::amount::Amount;
+use crate::models::coins;
+use crate::models::lists::{self, ListType, MarketType};
+use crate::models::marketplace_packages::Marketplace;
+use crate::models::offers::{self, OfferType};
+use crate::models::orderbooks::{self, Orderbook};
+use crate::models::orders::{self, OrderType};
+use crate::ObjectStatus;
+
+/// Ask, bid and trade events only name their orderbook, the coin they are
+/// priced in is the one the book was created with.
 #[derive(Debug)]
-pub enum OriginByteEvent {}
+pub enum OriginByteEvent {
+    OrderbookCreated(OrderbookCreated),
+    AskCreated(AskCreated),
+    AskClosed(AskClosed),
+    BidCreated(BidCreated),
+    BidClosed(BidClosed),
+    TradeFilled(TradeFilled),
+    MintCollection(MintCollectionWithSender),
+    Mint(MintWithSender),
+}
+
+impl From<OriginByteEvent> for EventIndex {
+    fn from(event: OriginByteEvent) -> Self { EventIndex::OriginByte(event) }
+}
 
-pub fn event_parse(e: &SuiEvent) -> Option<super::EventIndex> {
-    let event_data = e.parsed_json.clone();
-    let event_name = e.type_.name.clone().to_string();
-    let event_module = e.type_.module.to_string();
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrderbookCreated {
+    #[serde(deserialize_with = "layout::id")]
+    pub orderbook: String,
+    pub nft_type: String,
+    pub ft_type: String,
+}
 
-    match event_name.as_str() {
-        _ => None,
-    }
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AskCreated {
+    #[serde(deserialize_with = "layout::id")]
+    pub nft: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub orderbook: String,
+    #[serde(deserialize_with = "layout::address")]
+    pub owner: String,
+    pub price: Amount,
+    #[serde(deserialize_with = "layout::id")]
+    pub kiosk: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AskClosed {
+    #[serde(deserialize_with = "layout::id")]
+    pub nft: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub orderbook: String,
+    #[serde(deserialize_with = "layout::address")]
+    pub owner: String,
+    pub price: Amount,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BidCreated {
+    #[serde(deserialize_with = "layout::id")]
+    pub orderbook: String,
+    #[serde(deserialize_with = "layout::address")]
+    pub owner: String,
+    pub price: Amount,
+    #[serde(deserialize_with = "layout::id")]
+    pub kiosk: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BidClosed {
+    #[serde(deserialize_with = "layout::id")]
+    pub orderbook: String,
+    #[serde(deserialize_with = "layout::address")]
+    pub owner: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub kiosk: String,
+    pub price: Amount,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeFilled {
+    #[serde(deserialize_with = "layout::id")]
+    pub orderbook: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub nft: String,
+    #[serde(deserialize_with = "layout::address")]
+    pub buyer: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub buyer_kiosk: String,
+    #[serde(deserialize_with = "layout::address")]
+    pub seller: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub seller_kiosk: String,
+    pub price: Amount,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TypeName {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MintCollection {
+    #[serde(deserialize_with = "layout::id")]
+    pub collection_id: String,
+    pub type_name: TypeName,
+}
+
+#[derive(Clone, Debug)]
+pub struct MintCollectionWithSender {
+    pub collection_id: String,
+    /// Collection type, `0x` prefixed like the object types.
+    pub collection_type: String,
+    pub sender: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Mint {
+    #[serde(deserialize_with = "layout::id")]
+    pub collection_id: String,
+    #[serde(deserialize_with = "layout::id")]
+    pub object: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct MintWithSender {
+    pub collection_id: String,
+    pub object: String,
+    /// The `C` of `MintEvent<C>`.
+    pub collection_type: String,
+    pub sender: String,
+}
+
+/// Orderbook asks carry no id of their own, an NFT has at most one open
+/// ask per orderbook.
+fn ask_id(orderbook: &str, nft: &str) -> String {
+    format!("{}:{}", orderbook, nft)
+}
+
+/// Bids are keyed by the bidding kiosk and price, the same key the
+/// orderbook closes them by. Several open bids may share it, each close is
+/// tied to its event so a replay never closes another one.
+fn bid_id(orderbook: &str, kiosk: &str, price: Amount) -> String {
+    format!("{}:{}:{}", orderbook, kiosk, price)
+}
+
+/// Parse an event of an Origin Byte package. Both the NFT protocol and the
+/// liquidity layer may declare the `orderbook` events, only the protocol
+/// declares the mint ones.
+pub fn event_parse(
+    e: &SuiEvent,
+    marketplace: Marketplace,
+) -> Result<Option<super::EventIndex>> {
+    let module = e.type_.module.as_str();
+    let name = e.type_.name.as_str();
+
+    let event = match (marketplace, module, name) {
+        (_, "orderbook", "OrderbookCreatedEvent") => {
+            OriginByteEvent::OrderbookCreated(layout::decode(e)?)
+        }
+        (_, "orderbook", "AskCreatedEvent") => {
+            OriginByteEvent::AskCreated(layout::decode(e)?)
+        }
+        (_, "orderbook", "AskClosedEvent") => {
+            OriginByteEvent::AskClosed(layout::decode(e)?)
+        }
+        (_, "orderbook", "BidCreatedEvent") => {
+            OriginByteEvent::BidCreated(layout::decode(e)?)
+        }
+        (_, "orderbook", "BidClosedEvent") => {
+            OriginByteEvent::BidClosed(layout::decode(e)?)
+        }
+        (_, "orderbook", "TradeFilledEvent") => {
+            OriginByteEvent::TradeFilled(layout::decode(e)?)
+        }
+        (Marketplace::OriginByte, "collection", "MintCollectionEvent") => {
+            let mint: MintCollection = layout::decode(e)?;
+            let name = mint.type_name.name;
+            OriginByteEvent::MintCollection(MintCollectionWithSender {
+                collection_id: mint.collection_id,
+                collection_type: match name.starts_with("0x") {
+                    true => name,
+                    false => format!("0x{}", name),
+                },
+                sender: e.sender.to_string(),
+            })
+        }
+        (Marketplace::OriginByte, "mint_event", "MintEvent") => {
+            let mint: Mint = layout::decode(e)?;
+            let collection_type = match e.type_.type_params.first() {
+                Some(TypeTag::Struct(tag)) => tag.to_string(),
+                _ => anyhow::bail!("MintEvent without a collection type"),
+            };
+            OriginByteEvent::Mint(MintWithSender {
+                collection_id: mint.collection_id,
+                object: mint.object,
+                collection_type,
+                sender: e.sender.to_string(),
+            })
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(event.into()))
 }
 
 pub fn event_handle(
     e: &OriginByteEvent,
+    id: &EventID,
+    chain_id: i64,
     event_time: i64,
     pg: &mut PgConnection,
 ) -> Result<()> {
+    let time = NaiveDateTime::from_timestamp_millis(event_time).unwrap();
+
+    match e {
+        OriginByteEvent::OrderbookCreated(orderbook) => {
+            info!("orderbook {:?}", orderbook);
+            let ft_type = format!("0x{}", normalize_type(&orderbook.ft_type));
+            let coin_id = coins::resolve_id(pg, &ft_type, event_time)?;
+            orderbooks::insert(
+                pg,
+                &Orderbook {
+                    chain_id,
+                    orderbook_id: orderbook.orderbook.clone(),
+                    nft_type: format!(
+                        "0x{}",
+                        normalize_type(&orderbook.nft_type)
+                    ),
+                    coin_id,
+                    created_at: event_time,
+                },
+            )?;
+        }
+        OriginByteEvent::AskCreated(ask) => {
+            let coin_id =
+                orderbooks::query_coin_id(pg, chain_id, &ask.orderbook)?;
+            let list = lists::List {
+                chain_id,
+                coin_id,
+                list_id: ask_id(&ask.orderbook, &ask.nft),
+                list_time: time,
+                token_id: ask.nft.clone(),
+                seller_address: ask.owner.clone(),
+                seller_value: ask.price,
+                list_type: ListType::Listed,
+                market_type: MarketType::OriginByteKiosk,
+                expire_time: None,
+                created_at: Some(Utc::now().naive_utc()),
+                updated_at: Some(Utc::now().naive_utc()),
+                tx_digest: id.tx_digest.to_string(),
+                event_seq: id.event_seq as i64,
+            };
+            info!("ask {:?}", list);
+            lists::batch_insert(pg, &vec![list])?;
+        }
+        OriginByteEvent::AskClosed(ask) => {
+            info!("ask_closed {:?}", ask);
+            lists::close_by_event(
+                pg,
+                chain_id,
+                &ask_id(&ask.orderbook, &ask.nft),
+                ListType::Canceled,
+                &id.tx_digest.to_string(),
+                id.event_seq as i64,
+                time,
+            )?;
+        }
+        OriginByteEvent::BidCreated(bid) => {
+            let coin_id =
+                orderbooks::query_coin_id(pg, chain_id, &bid.orderbook)?;
+            let offer = offers::Offer {
+                chain_id,
+                coin_id,
+                offer_id: bid_id(&bid.orderbook, &bid.kiosk, bid.price),
+                list_id: bid.orderbook.clone(),
+                buyer_address: bid.owner.clone(),
+                offer_value: bid.price,
+                offer_type: OfferType::Listed,
+                expire_time: None,
+                offer_time: time,
+                created_at: Some(Utc::now().naive_utc()),
+                updated_at: Some(Utc::now().naive_utc()),
+                tx_digest: id.tx_digest.to_string(),
+                event_seq: id.event_seq as i64,
+                // orderbook bids can be filled by any NFT of the book.
+                token_id: None,
+                collection_type: None,
+            };
+            info!("bid {:?}", offer);
+            offers::batch_insert(pg, &vec![offer])?;
+        }
+        OriginByteEvent::BidClosed(bid) => {
+            info!("bid_closed {:?}", bid);
+            offers::close_by_event(
+                pg,
+                chain_id,
+                &bid_id(&bid.orderbook, &bid.kiosk, bid.price),
+                OfferType::Canceled,
+                &id.tx_digest.to_string(),
+                id.event_seq as i64,
+                time,
+            )?;
+        }
+        OriginByteEvent::TradeFilled(trade) => {
+            // the trade consumed either the open ask or an open bid, never
+            // both: a bid is only closed when no ask of the NFT was open.
+            let tx_digest = id.tx_digest.to_string();
+            let event_seq = id.event_seq as i64;
+            let list_id = ask_id(&trade.orderbook, &trade.nft);
+            let asks_closed = lists::close_by_event(
+                pg,
+                chain_id,
+                &list_id,
+                ListType::Sold,
+                &tx_digest,
+                event_seq,
+                time,
+            )?;
+            let offer_id =
+                bid_id(&trade.orderbook, &trade.buyer_kiosk, trade.price);
+            let bids_closed = match asks_closed {
+                0 => offers::close_by_event(
+                    pg,
+                    chain_id,
+                    &offer_id,
+                    OfferType::Sold,
+                    &tx_digest,
+                    event_seq,
+                    time,
+                )?,
+                _ => 0,
+            };
+
+            let order = orders::Order {
+                chain_id,
+                coin_id: orderbooks::query_coin_id(
+                    pg,
+                    chain_id,
+                    &trade.orderbook,
+                )?,
+                list_id,
+                token_id: trade.nft.clone(),
+                offer_id: if bids_closed > 0 {
+                    Some(offer_id)
+                } else {
+                    None
+                },
+                seller_address: trade.seller.clone(),
+                buyer_address: trade.buyer.clone(),
+                order_type: if bids_closed > 0 {
+                    OrderType::Offer
+                } else {
+                    OrderType::Sold
+                },
+                value: trade.price,
+                sell_time: time,
+                created_at: Some(Utc::now().naive_utc()),
+                updated_at: Some(Utc::now().naive_utc()),
+                tx_digest,
+                event_seq,
+            };
+            info!("trade {:?}", order);
+            orders::batch_insert(pg, &vec![order])?;
+        }
+        OriginByteEvent::MintCollection(_) | OriginByteEvent::Mint(_) => {}
+    }
+
     Ok(())
 }
+
+/// Activities for Origin Byte collection and NFT mints. A collection with
+/// a Display already got its created activity from the collection handler,
+/// and NFTs of such a collection are keyed like the token handler's mints
+/// so the same mint is stored once.
+pub fn mint_activities(
+    events: &Vec<(EventID, EventIndex)>,
+    object_changes: &Vec<(ObjectStatus, SuiObjectData, String, u64)>,
+    collection_types: &HashMap<String, String>,
+    chain_id: i64,
+    event_time: i64,
+) -> Vec<Activity> {
+    let time = NaiveDateTime::from_timestamp_millis(event_time).unwrap();
+
+    events
+        .iter()
+        .filter_map(|(id, e)| {
+            let (
+                transfer_type,
+                collection_id,
+                token_id,
+                collection_type,
+                sender,
+            ) = match e {
+                EventIndex::OriginByte(OriginByteEvent::MintCollection(
+                    mint,
+                )) if !collection_types.contains_key(&mint.collection_type) => {
+                    (
+                        ActivityType::Created,
+                        &mint.collection_id,
+                        "",
+                        &mint.collection_type,
+                        &mint.sender,
+                    )
+                }
+                EventIndex::OriginByte(OriginByteEvent::Mint(mint)) => (
+                    ActivityType::Minted,
+                    &mint.collection_id,
+                    mint.object.as_str(),
+                    &mint.collection_type,
+                    &mint.sender,
+                ),
+                _ => return None,
+            };
+
+            let collection_id = collection_types
+                .get(collection_type)
+                .unwrap_or(collection_id)
+                .clone();
+            // minted into a kiosk the NFT is wrapped right away and never
+            // shows up as a created object.
+            let version = object_changes
+                .iter()
+                .find(|(_, obj, _, _)| obj.object_id.to_string() == token_id)
+                .map(|(_, obj, _, _)| obj.version.value() as i64)
+                .unwrap_or_default();
+
+            Some(Activity {
+                chain_id,
+                version,
+                tx: id.tx_digest.to_string(),
+                event_account_address: sender.clone(),
+                event_creation_number: 0,
+                event_sequence_number: id.event_seq as i64,
+                collection_data_id_hash: collection_id,
+                token_data_id_hash: token_id.to_string(),
+                property_version: version,
+                creator_address: "".to_string(),
+                collection_name: collection_type
+                    .split("::")
+                    .last()
+                    .unwrap_or_default()
+                    .to_string(),
+                name: "".to_string(),
+                transfer_type,
+                from_address: Some(sender.clone()),
+                to_address: None,
+                token_amount: Amount(0),
+                coin_type: None,
+                coin_amount: Amount(0),
+                transaction_timestamp: time,
+                created_at: Utc::now().naive_utc(),
+                updated_at: Utc::now().naive_utc(),
+            })
+        })
+        .collect()
+}
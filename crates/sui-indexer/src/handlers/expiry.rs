@@ -0,0 +1,223 @@
+use anyhow::Result;
+use chrono::{NaiveDateTime, Utc};
+use diesel::PgConnection;
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::info;
+
+use crate::handlers::{CheckpointHandler, CheckpointState, HandlerContext};
+use crate::indexer::receiver::IndexingMessage;
+use crate::indexer::CheckpointData;
+use crate::models::activities::{Activity, ActivityType};
+use crate::models::amount::Amount;
+use crate::models::{coins, lists, offers, tokens};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiredKind {
+    List,
+    Offer,
+}
+
+impl ExpiredKind {
+    pub fn to_str(&self) -> &str {
+        match self {
+            ExpiredKind::List => "list",
+            ExpiredKind::Offer => "offer",
+        }
+    }
+}
+
+/// A listing or offer that expired, published so caches can drop it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Expired {
+    pub chain_id: i64,
+    pub kind: ExpiredKind,
+    /// `list_id` or `offer_id`.
+    pub id: String,
+    /// Unset for collection-wide bids.
+    pub token_id: Option<String>,
+    pub collection_type: Option<String>,
+    /// Seller or bidder.
+    pub owner: String,
+    pub coin_id: i32,
+    pub value: Amount,
+    /// Milliseconds since the epoch.
+    pub expire_time: i64,
+    /// Event that created the listing or offer, keys the activity.
+    #[serde(skip)]
+    pub tx_digest: String,
+    #[serde(skip)]
+    pub event_seq: i64,
+}
+
+impl From<&lists::QueryList> for Expired {
+    fn from(list: &lists::QueryList) -> Self {
+        Expired {
+            chain_id: list.chain_id,
+            kind: ExpiredKind::List,
+            id: list.list_id.clone(),
+            token_id: Some(list.token_id.clone()),
+            collection_type: None,
+            owner: list.seller_address.clone(),
+            coin_id: list.coin_id,
+            value: list.seller_value,
+            expire_time: list
+                .expire_time
+                .map(|t| t.timestamp_millis())
+                .unwrap_or_default(),
+            tx_digest: list.tx_digest.clone(),
+            event_seq: list.event_seq,
+        }
+    }
+}
+
+impl From<&offers::QueryOffer> for Expired {
+    fn from(offer: &offers::QueryOffer) -> Self {
+        Expired {
+            chain_id: offer.chain_id,
+            kind: ExpiredKind::Offer,
+            id: offer.offer_id.clone(),
+            token_id: offer.token_id.clone(),
+            collection_type: offer.collection_type.clone(),
+            owner: offer.buyer_address.clone(),
+            coin_id: offer.coin_id,
+            value: offer.offer_value,
+            expire_time: offer
+                .expire_time
+                .map(|t| t.timestamp_millis())
+                .unwrap_or_default(),
+            tx_digest: offer.tx_digest.clone(),
+            event_seq: offer.event_seq,
+        }
+    }
+}
+
+fn expired_activity(
+    expired: &Expired,
+    collection_types: &HashMap<String, String>,
+    checkpoint: i64,
+    time: NaiveDateTime,
+    pg: &mut PgConnection,
+) -> Result<Activity> {
+    let token = match &expired.token_id {
+        Some(token_id) => tokens::query_token(pg, expired.chain_id, token_id)?,
+        None => None,
+    };
+    let (collection_id, collection_name, name) = match token {
+        Some(token) => {
+            (token.collection_id, token.collection_name, token.token_name)
+        }
+        None => (
+            expired
+                .collection_type
+                .as_ref()
+                .map(|t| collection_types.get(t).unwrap_or(t).clone())
+                .unwrap_or_default(),
+            "".to_string(),
+            "".to_string(),
+        ),
+    };
+
+    Ok(Activity {
+        chain_id: expired.chain_id,
+        // expiring changes no object, keyed by checkpoint and the event
+        // that created the listing or offer.
+        version: checkpoint,
+        tx: expired.tx_digest.clone(),
+        event_account_address: expired.owner.clone(),
+        event_creation_number: 0,
+        event_sequence_number: expired.event_seq,
+        collection_data_id_hash: collection_id,
+        token_data_id_hash: expired.token_id.clone().unwrap_or_default(),
+        property_version: checkpoint,
+        creator_address: "".to_string(),
+        collection_name,
+        name,
+        transfer_type: match expired.kind {
+            ExpiredKind::List => ActivityType::Expired,
+            ExpiredKind::Offer => ActivityType::OfferExpired,
+        },
+        from_address: Some(expired.owner.clone()),
+        to_address: None,
+        token_amount: expired.value,
+        coin_type: coins::query_type(pg, expired.coin_id)?,
+        coin_amount: Amount(0),
+        transaction_timestamp: time,
+        created_at: Utc::now().naive_utc(),
+        updated_at: Utc::now().naive_utc(),
+    })
+}
+
+/// Expires listings and offers whose `expire_time` has passed. Checkpoint
+/// time is cut into `interval_ms` buckets, the first checkpoint of each
+/// bucket expires what expired before the bucket started. A replay, or a
+/// restart within a bucket, sweeps with the same cutoff as the live run.
+/// Register it after the marketplace handler.
+pub struct ExpiryHandler {
+    interval_ms: i64,
+    /// Bucket of the last sweep, unset until the first one.
+    last_bucket: Option<i64>,
+}
+
+impl ExpiryHandler {
+    pub fn new(interval_ms: i64) -> Self {
+        Self {
+            interval_ms: interval_ms.max(1),
+            last_bucket: None,
+        }
+    }
+}
+
+impl CheckpointHandler for ExpiryHandler {
+    fn name(&self) -> &str { "expiry" }
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        (checkpoint, _, _, _): &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let now_ms = checkpoint.timestamp_ms as i64;
+        let bucket = now_ms / self.interval_ms;
+        if self.last_bucket == Some(bucket) {
+            return Ok(());
+        }
+        let now = NaiveDateTime::from_timestamp_millis(now_ms).unwrap();
+        let cutoff =
+            NaiveDateTime::from_timestamp_millis(bucket * self.interval_ms)
+                .unwrap();
+        let chain_id = ctx.network.chain_id();
+
+        let expired = lists::expire(conn, chain_id, cutoff)?
+            .iter()
+            .map(Expired::from)
+            .chain(
+                offers::expire(conn, chain_id, cutoff)?
+                    .iter()
+                    .map(Expired::from),
+            )
+            .collect::<Vec<Expired>>();
+        if expired.len() > 0 {
+            info!(
+                checkpoint = checkpoint.sequence_number,
+                expired = expired.len(),
+                "Expired listings and offers"
+            );
+        }
+
+        for e in expired {
+            state.activities.push(expired_activity(
+                &e,
+                &ctx.collection_types,
+                checkpoint.sequence_number as i64,
+                now,
+                conn,
+            )?);
+            state.messages.push(IndexingMessage::Expired(e));
+        }
+        self.last_bucket = Some(bucket);
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::{anyhow, Result};
+use diesel::PgConnection;
+use sui_sdk::rpc_types::{Checkpoint, SuiObjectData, SuiParsedData};
+use tracing::warn;
+
+use crate::handlers::{
+    failed_object, CheckpointHandler, CheckpointState, HandlerContext,
+};
+use crate::indexer::CheckpointData;
+use crate::models::failed_items::FailedItem;
+use crate::models::kiosks::{batch_change, Kiosk};
+use crate::ObjectStatus;
+
+const KIOSK_OWNER_CAP: &str = "0x2::kiosk::KioskOwnerCap";
+
+pub fn parse_kiosks(
+    checkpoint: &Checkpoint,
+    object_changes: &Vec<(ObjectStatus, SuiObjectData, String, u64)>,
+    chain_id: i64,
+    failures: &mut Vec<FailedItem>,
+) -> Vec<Kiosk> {
+    object_changes
+        .iter()
+        .filter(|(_, obj, _, _)| {
+            obj.type_.as_ref().map(|t| t.to_string()).as_deref()
+                == Some(KIOSK_OWNER_CAP)
+        })
+        .filter_map(|(_, obj, _, timestamp)| {
+            match parse_owner_cap(obj, chain_id, *timestamp) {
+                Ok(kiosk) => Some(kiosk),
+                Err(e) => {
+                    warn!(object_id = %obj.object_id, "Failed to parse KioskOwnerCap: {:#}", e);
+                    failures.push(failed_object(chain_id, checkpoint, obj, &e));
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn parse_owner_cap(
+    obj: &SuiObjectData,
+    chain_id: i64,
+    timestamp: u64,
+) -> Result<Kiosk> {
+    let fields = match obj.content.as_ref() {
+        Some(SuiParsedData::MoveObject(parse_obj)) => {
+            parse_obj.fields.clone().to_json_value()
+        }
+        _ => return Err(anyhow!("KioskOwnerCap has no content")),
+    };
+    let kiosk_id = fields["for"]
+        .as_str()
+        .ok_or_else(|| anyhow!("KioskOwnerCap without a kiosk"))?;
+    let owner_address = obj
+        .owner
+        .as_ref()
+        .and_then(|owner| owner.get_owner_address().ok())
+        .ok_or_else(|| anyhow!("KioskOwnerCap is not address owned"))?;
+
+    Ok(Kiosk {
+        chain_id,
+        kiosk_id: kiosk_id.to_string(),
+        owner_cap_id: obj.object_id.to_string(),
+        owner_address: owner_address.to_string(),
+        version: obj.version.value() as i64,
+        created_at: timestamp as i64,
+        updated_at: timestamp as i64,
+    })
+}
+
+/// Tracks who holds each kiosk's `KioskOwnerCap`. Register it before the
+/// token and marketplace handlers so tokens in a kiosk and kiosk sellers
+/// resolve to the current owner.
+pub struct KioskHandler;
+
+impl CheckpointHandler for KioskHandler {
+    fn name(&self) -> &str { "kiosk" }
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        (checkpoint, _, object_changed, _): &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let kiosks = parse_kiosks(
+            checkpoint,
+            object_changed,
+            ctx.network.chain_id(),
+            &mut state.failures,
+        );
+        if kiosks.len() > 0 {
+            batch_change(conn, ctx.network.chain_id(), &kiosks)?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,141 @@
 pub mod activity;
+pub mod attributes;
+pub mod burn;
+pub mod coin;
 pub mod collection;
+pub mod custody;
 pub mod event;
+pub mod expiry;
+pub mod kiosk;
 pub mod kiosk_event;
+pub mod owner;
+pub mod supply;
 pub mod token;
+pub mod transfer_policy;
+
+use anyhow::Result;
+use diesel::PgConnection;
+use std::collections::HashMap;
+use sui_sdk::rpc_types::{Checkpoint, SuiEvent, SuiObjectData};
+use sui_sdk::types::event::EventID;
+
+use crate::config::Network;
+use crate::indexer::receiver::IndexingMessage;
+use crate::indexer::CheckpointData;
+use crate::models::activities::Activity;
+use crate::models::collections::Collection;
+use crate::models::failed_items::{FailedItem, FailedItemKind};
+use crate::models::tokens::Token;
+use crate::ObjectStatus;
+
+use self::event::EventIndex;
+
+/// A step of the per-checkpoint pipeline.
+///
+/// Handlers run in registration order inside the checkpoint's Postgres
+/// transaction, so anything they write commits or rolls back together with
+/// the cursor. Results meant for later handlers go into `CheckpointState`.
+pub trait CheckpointHandler: Send {
+    fn name(&self) -> &str;
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        checkpoint: &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()>;
+}
+
+/// State shared by all handlers that outlives a single checkpoint.
+pub struct HandlerContext {
+    pub network: Network,
+    /// Collection type to collection (Display object) id.
+    pub collection_types: HashMap<String, String>,
+}
+
+/// What the handlers that already ran produced for the current checkpoint.
+#[derive(Default)]
+pub struct CheckpointState {
+    pub collections: Vec<(ObjectStatus, Collection)>,
+    pub tokens: Vec<(ObjectStatus, (Token, String))>,
+    pub events: Vec<(EventID, EventIndex)>,
+    pub activities: Vec<Activity>,
+    /// Published to RabbitMQ once the checkpoint transaction committed.
+    pub messages: Vec<IndexingMessage>,
+    /// Events and objects that failed to parse, stored in `failed_items`.
+    pub failures: Vec<FailedItem>,
+}
+
+/// Dead-letter row for an event that failed to parse.
+pub fn failed_event(
+    chain_id: i64,
+    checkpoint: &Checkpoint,
+    event: &SuiEvent,
+    error: &anyhow::Error,
+) -> FailedItem {
+    FailedItem {
+        chain_id,
+        checkpoint: checkpoint.sequence_number as i64,
+        tx_digest: event.id.tx_digest.to_string(),
+        item_kind: FailedItemKind::Event,
+        item_key: event.id.event_seq.to_string(),
+        raw_json: serde_json::to_string(event).unwrap_or_default(),
+        error: format!("{:#}", error),
+    }
+}
+
+/// Dead-letter row for an object that failed to parse.
+pub fn failed_object(
+    chain_id: i64,
+    checkpoint: &Checkpoint,
+    object: &SuiObjectData,
+    error: &anyhow::Error,
+) -> FailedItem {
+    FailedItem {
+        chain_id,
+        checkpoint: checkpoint.sequence_number as i64,
+        tx_digest: object
+            .previous_transaction
+            .map(|tx| tx.to_string())
+            .unwrap_or_default(),
+        item_kind: FailedItemKind::Object,
+        item_key: object.object_id.to_string(),
+        raw_json: serde_json::to_string(object).unwrap_or_default(),
+        error: format!("{:#}", error),
+    }
+}
+
+#[derive(Default)]
+pub struct HandlerRegistry {
+    handlers: Vec<Box<dyn CheckpointHandler>>,
+}
+
+impl HandlerRegistry {
+    pub fn new() -> Self { Self { handlers: vec![] } }
+
+    pub fn register(&mut self, handler: Box<dyn CheckpointHandler>) {
+        self.handlers.push(handler);
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.handlers.iter().map(|h| h.name()).collect()
+    }
+
+    pub fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        checkpoint: &CheckpointData,
+        conn: &mut PgConnection,
+    ) -> Result<CheckpointState> {
+        let mut state = CheckpointState::default();
+        for handler in self.handlers.iter_mut() {
+            handler
+                .handle(ctx, checkpoint, &mut state, conn)
+                .map_err(|e| {
+                    anyhow::anyhow!("Handler {} failed: {}", handler.name(), e)
+                })?;
+        }
+        Ok(state)
+    }
+}
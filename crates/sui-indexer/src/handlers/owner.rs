@@ -0,0 +1,100 @@
+use anyhow::Result;
+use diesel::PgConnection;
+use std::collections::HashMap;
+use sui_sdk::rpc_types::SuiObjectData;
+use sui_sdk::types::object::Owner;
+
+use crate::models::kiosks;
+use crate::models::tokens::{self, OwnerKind, Token};
+use crate::ObjectStatus;
+
+/// Parents walked before giving up, dynamic object fields add one per level.
+const MAX_DEPTH: usize = 8;
+
+/// Walk `owner` up through parent objects to an address. Parents are
+/// looked up among the objects of the checkpoint, then among the kiosks
+/// and tokens already stored. Unset when the chain leaves what is known.
+fn resolve(
+    conn: &mut PgConnection,
+    chain_id: i64,
+    objects: &HashMap<String, &SuiObjectData>,
+    owner: Option<&Owner>,
+) -> Result<Option<(Option<String>, OwnerKind)>> {
+    let mut owner = owner;
+    let mut through_object = false;
+    for _ in 0..MAX_DEPTH {
+        let parent = match owner {
+            Some(Owner::AddressOwner(address)) => {
+                let kind = if through_object {
+                    OwnerKind::Object
+                } else {
+                    OwnerKind::Address
+                };
+                return Ok(Some((Some(address.to_string()), kind)));
+            }
+            Some(Owner::Shared { .. }) if through_object => {
+                return Ok(Some((None, OwnerKind::Object)))
+            }
+            Some(Owner::Shared { .. }) => {
+                return Ok(Some((None, OwnerKind::Shared)))
+            }
+            Some(Owner::Immutable) if through_object => {
+                return Ok(Some((None, OwnerKind::Object)))
+            }
+            Some(Owner::Immutable) => {
+                return Ok(Some((None, OwnerKind::Immutable)))
+            }
+            Some(Owner::ObjectOwner(parent)) => parent.to_string(),
+            None => return Ok(None),
+        };
+
+        // the kiosk handler runs first, so the table has this checkpoint's
+        // kiosks too.
+        if let Some(owner) = kiosks::query_owner(conn, chain_id, &parent)? {
+            return Ok(Some((Some(owner), OwnerKind::Kiosk)));
+        }
+        through_object = true;
+        match objects.get(&parent) {
+            Some(obj) => owner = obj.owner.as_ref(),
+            // an NFT owned by another indexed NFT.
+            None => {
+                return Ok(tokens::query_token(conn, chain_id, &parent)?
+                    .map(|t| (t.owner_address, OwnerKind::Object)))
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Set `owner_address` and `owner_kind` of the parsed tokens. Tokens in a
+/// kiosk resolve to the kiosk owner and tokens owned by another object to
+/// the address holding it. When the ownership chain cannot be followed,
+/// e.g. the token changed without the dynamic field holding it, the stored
+/// owner is kept.
+pub fn resolve_owners(
+    conn: &mut PgConnection,
+    chain_id: i64,
+    object_changes: &Vec<(ObjectStatus, SuiObjectData, String, u64)>,
+    parsed: &mut Vec<(ObjectStatus, (Token, String))>,
+) -> Result<()> {
+    let objects = object_changes
+        .iter()
+        .map(|(_, obj, _, _)| (obj.object_id.to_string(), obj))
+        .collect::<HashMap<String, &SuiObjectData>>();
+
+    for (_, (token, _)) in parsed.iter_mut() {
+        let owner = objects
+            .get(&token.token_id)
+            .and_then(|obj| obj.owner.as_ref());
+        let (owner_address, owner_kind) =
+            match resolve(conn, chain_id, &objects, owner)? {
+                Some(resolved) => resolved,
+                None => tokens::query_token(conn, chain_id, &token.token_id)?
+                    .and_then(|t| t.owner_kind.map(|k| (t.owner_address, k)))
+                    .unwrap_or((None, OwnerKind::Object)),
+            };
+        token.owner_address = owner_address;
+        token.owner_kind = Some(owner_kind);
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use anyhow::Result;
+use diesel::PgConnection;
+use std::collections::{HashMap, HashSet};
+use tracing::info;
+
+use crate::handlers::{CheckpointHandler, CheckpointState, HandlerContext};
+use crate::indexer::receiver::IndexingMessage;
+use crate::indexer::CheckpointData;
+use crate::models::collections::{self, refresh_counters, Counters};
+
+/// Recounts the minted, burned, circulating and holder counters of every
+/// collection whose tokens changed, in the checkpoint transaction, and sets
+/// them on the collection messages of the checkpoint. Recounting rather
+/// than applying deltas keeps replayed checkpoints from counting twice.
+/// Register it after the collection, token, burn and custody handlers.
+pub struct SupplyHandler;
+
+impl CheckpointHandler for SupplyHandler {
+    fn name(&self) -> &str { "supply" }
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        _checkpoint: &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let ids = state
+            .tokens
+            .iter()
+            .map(|(_, (t, _))| t.collection_id.clone())
+            .chain(
+                state
+                    .collections
+                    .iter()
+                    .map(|(_, c)| c.collection_id.clone()),
+            )
+            .collect::<HashSet<String>>()
+            .into_iter()
+            .collect::<Vec<String>>();
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let counters = refresh_counters(conn, ctx.network.chain_id(), &ids)?
+            .into_iter()
+            .map(|c| (c.collection_id.clone(), c))
+            .collect::<HashMap<String, Counters>>();
+        for message in state.messages.iter_mut() {
+            if let IndexingMessage::Collection((_, collection)) = message {
+                if let Some(c) = counters.get(&collection.collection_id) {
+                    collection.minted = c.minted;
+                    collection.burned = c.burned;
+                    collection.supply = c.minted - c.burned;
+                    collection.holders = c.holders;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Recount the counters of every collection of the chain, `batch_size`
+/// collections at a time, e.g. after tokens were fixed up by hand.
+pub fn backfill(
+    conn: &mut PgConnection,
+    chain_id: i64,
+    batch_size: i64,
+) -> Result<usize> {
+    let mut after = String::new();
+    let mut counted = 0;
+    loop {
+        let page =
+            collections::query_ids_page(conn, chain_id, &after, batch_size)?;
+        let last = match page.last() {
+            Some(id) => id.clone(),
+            None => break,
+        };
+        refresh_counters(conn, chain_id, &page)?;
+        counted += page.len();
+        info!(collections = counted, last = last, "Counters recounted");
+        after = last;
+    }
+    info!(collections = counted, "Counters backfill finished");
+    Ok(counted)
+}
@@ -1,83 +1,184 @@
+use crate::config::Network;
+use crate::handlers::attributes::attribute_rows;
+use crate::handlers::owner::resolve_owners;
+use crate::handlers::transfer_policy::{fill_token_royalties, normalize_type};
+use crate::handlers::{CheckpointHandler, CheckpointState, HandlerContext};
+use crate::indexer::receiver::IndexingMessage;
+use crate::indexer::CheckpointData;
 use crate::models::activities::{Activity, ActivityType};
-use crate::models::tokens::{Token, TokenStatus};
+use crate::models::token_attributes;
+use crate::models::token_ownership_history::{self, Ownership};
+use crate::models::tokens::{batch_change, Token, TokenStatus};
 use crate::ObjectStatus;
 use anyhow::Result;
+use diesel::PgConnection;
+use serde_json::Value;
 
-use std::collections::HashMap;
-use sui_sdk::rpc_types::SuiObjectData;
+use std::collections::{HashMap, HashSet};
+use sui_sdk::rpc_types::{SuiObjectData, SuiParsedData};
+use tracing::warn;
+
+/// NFT types indexed without a Display. Patterns are written like object
+/// types, a trailing `*` matching any type that starts with the rest.
+#[derive(Clone, Debug, Default)]
+pub struct NftTypes {
+    exact: HashSet<String>,
+    prefixes: Vec<String>,
+}
+
+impl NftTypes {
+    pub fn new(patterns: &Vec<String>) -> Self {
+        let mut nft_types = Self::default();
+        for pattern in patterns.iter().map(|p| p.trim()) {
+            if pattern.is_empty() {
+                continue;
+            }
+            // accept `TypeName` style addresses too.
+            let pattern = format!("0x{}", normalize_type(pattern));
+            match pattern.strip_suffix('*') {
+                Some(prefix) => nft_types.prefixes.push(prefix.to_string()),
+                None => {
+                    nft_types.exact.insert(pattern);
+                }
+            }
+        }
+        nft_types
+    }
+
+    pub fn matches(&self, object_type: &str) -> bool {
+        self.exact.contains(object_type)
+            || self.prefixes.iter().any(|p| object_type.starts_with(p))
+    }
+}
+
+/// Display-like metadata of an NFT without a Display: its string fields,
+/// with the image under `image_url`, the JSON of its attributes and of all
+/// its fields.
+fn fields_metadata(
+    obj: &SuiObjectData,
+) -> Option<(HashMap<String, String>, Option<String>, Option<String>)> {
+    let fields = match obj.content.as_ref() {
+        Some(SuiParsedData::MoveObject(parse_obj)) => {
+            parse_obj.fields.clone().to_json_value()
+        }
+        _ => return None,
+    };
+    let mut kv_set = fields
+        .as_object()?
+        .iter()
+        .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+        .collect::<HashMap<String, String>>();
+    if !kv_set.contains_key("image_url") {
+        if let Some(image_url) =
+            kv_set.get("url").or_else(|| kv_set.get("img_url")).cloned()
+        {
+            kv_set.insert("image_url".to_string(), image_url);
+        }
+    }
+
+    // a `VecMap`, possibly wrapped in an OB style `Attributes { map }`.
+    // Values are kept as they are, `vector`s and structs included.
+    let attributes = &fields["attributes"];
+    let attributes = [
+        &attributes["contents"],
+        &attributes["map"]["contents"],
+        attributes,
+    ]
+    .into_iter()
+    .find_map(|entries| {
+        let kv = entries
+            .as_array()?
+            .iter()
+            .map(|e| Some((e["key"].as_str()?.to_string(), e["value"].clone())))
+            .collect::<Option<serde_json::Map<String, Value>>>()?;
+        Some(kv).filter(|kv| !kv.is_empty())
+    })
+    .and_then(|kv| serde_json::to_string(&kv).ok());
+
+    Some((kv_set, attributes, serde_json::to_string(&fields).ok()))
+}
 
 pub fn parse_tokens(
     object_changes: &Vec<(ObjectStatus, SuiObjectData, String, u64)>,
+    network: &Network,
     coll_set: &mut HashMap<String, String>,
+    nft_types: &NftTypes,
 ) -> Result<Vec<(ObjectStatus, (Token, String))>> {
     let tokens = object_changes
         .into_iter()
         .filter_map(|(status, obj, sender, timestamp)| {
-            let object_type = obj.type_.as_ref().unwrap().clone().to_string();
-            if let Some(display) = &obj.display {
-                if let Some(kv_set) = &display.data {
-                    let collection_id =
-                        coll_set.get(&object_type).expect(&*format!(
-                            "collection not found in kv store {}",
-                            object_type
-                        ));
-
-                    let name = kv_set
-                        .get(&"name".to_string())
-                        .unwrap_or(&"".to_string())
-                        .clone();
-                    let image_url = kv_set
-                        .get(&"image_url".to_string())
-                        .unwrap_or(&"".to_string())
-                        .clone();
-                    let owner_address = obj.owner.as_ref().map(|owner| {
-                        owner
-                            .get_owner_address()
-                            .unwrap_or_default()
-                            .to_string()
-                    });
-
-                    let display_json = serde_json::to_string(&kv_set).unwrap();
-
-                    let tx: Option<String> =
-                        if let Some(ok) = obj.previous_transaction {
-                            Some(ok.to_string())
-                        } else {
-                            None
-                        };
-
-                    return Some((
-                        status.clone(),
-                        (
-                            Token {
-                                chain_id: 1,
-                                token_id: obj.object_id.to_string(),
-                                collection_id: collection_id.to_string(),
-                                collection_type: object_type.clone(),
-                                creator_address: "".to_string(),
-                                collection_name: "".to_string(),
-                                token_name: name,
-                                attributes: Some(display_json.clone()),
-                                version: obj.version.value() as i64,
-                                payee_address: "".to_string(),
-                                royalty_points_numerator: 0,
-                                royalty_points_denominator: 0,
-                                owner_address,
-                                metadata_uri: image_url,
-                                metadata_json: Some(display_json),
-                                image: None,
-                                tx,
-                                status: TokenStatus::EXIST,
-                                created_at: Some(*timestamp as i64),
-                                updated_at: Some(*timestamp as i64),
-                            },
-                            sender.clone(),
-                        ),
-                    ));
+            let object_type = obj.type_.as_ref()?.to_string();
+            let display = obj.display.as_ref().and_then(|d| d.data.as_ref());
+            let (kv_set, attributes, metadata_json) = match display {
+                Some(kv_set) => {
+                    let display_json = serde_json::to_string(&kv_set).ok();
+                    let kv_set: HashMap<String, String> =
+                        kv_set.clone().into_iter().collect();
+                    (kv_set, display_json.clone(), display_json)
                 }
-                return None;
-            }
-            return None;
+                None if nft_types.matches(&object_type) => {
+                    fields_metadata(obj)?
+                }
+                None => return None,
+            };
+
+            // types without a Display have no collection object, they are
+            // grouped by type.
+            let collection_id = match coll_set.get(&object_type) {
+                Some(collection_id) => collection_id.clone(),
+                None => {
+                    if display.is_some() {
+                        warn!(%object_type, "Display type without a collection");
+                    }
+                    object_type.clone()
+                }
+            };
+
+            let name = kv_set
+                .get(&"name".to_string())
+                .unwrap_or(&"".to_string())
+                .clone();
+            let image_url = kv_set
+                .get(&"image_url".to_string())
+                .unwrap_or(&"".to_string())
+                .clone();
+            let tx: Option<String> = if let Some(ok) = obj.previous_transaction
+            {
+                Some(ok.to_string())
+            } else {
+                None
+            };
+
+            Some((
+                status.clone(),
+                (
+                    Token {
+                        chain_id: network.chain_id(),
+                        token_id: obj.object_id.to_string(),
+                        collection_id,
+                        collection_type: object_type.clone(),
+                        creator_address: "".to_string(),
+                        collection_name: "".to_string(),
+                        token_name: name,
+                        attributes,
+                        version: obj.version.value() as i64,
+                        payee_address: "".to_string(),
+                        royalty_points_numerator: 0,
+                        royalty_points_denominator: 0,
+                        // set by `owner::resolve_owners`.
+                        owner_address: None,
+                        metadata_uri: image_url,
+                        metadata_json,
+                        image: None,
+                        tx,
+                        status: TokenStatus::EXIST,
+                        created_at: Some(*timestamp as i64),
+                        updated_at: Some(*timestamp as i64),
+                        owner_kind: None,
+                    },
+                    sender.clone(),
+                ),
+            ))
         })
         .collect::<Vec<(ObjectStatus, (Token, String))>>();
 
@@ -197,24 +298,116 @@ pub fn token_indexer_work(
         ret_tokens = tokens_for_db;
     }
 
-    // let deleted_tokens = tokens
-    //     .iter()
-    //     .filter_map(|(objects, token)| {
-    //         if *objects == ObjectStatus::Deleted
-    //             || *objects == ObjectStatus::UnwrappedThenDeleted
-    //         {
-    //             return Some(token.clone());
-    //         }
-    //         None
-    //     })
-    //     .collect::<Vec<(Token, String)>>();
-    // if deleted_tokens.len() > 0 {
-    //     for t in deleted_tokens {
-    //         set_status_delete(pg, &t.0.token_id).map_err(|e| {
-    //             anyhow!("DeleteTokens failed {}", e.to_string())
-    //         })?;
-    //     }
-    // }
-
     Ok((ret_tokens, ret_act))
 }
+
+/// Indexes objects whose type has a Display registered, or is in
+/// `nft_types`, as tokens.
+pub struct TokenHandler {
+    nft_types: NftTypes,
+}
+
+impl TokenHandler {
+    pub fn new(nft_types: NftTypes) -> Self { Self { nft_types } }
+}
+
+impl CheckpointHandler for TokenHandler {
+    fn name(&self) -> &str { "token" }
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        (checkpoint, _, object_changed, _): &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let mut tokens = parse_tokens(
+            object_changed,
+            &ctx.network,
+            &mut ctx.collection_types,
+            &self.nft_types,
+        )?;
+        fill_token_royalties(conn, ctx.network.chain_id(), &mut tokens)?;
+        resolve_owners(
+            conn,
+            ctx.network.chain_id(),
+            object_changed,
+            &mut tokens,
+        )?;
+
+        // unwraps are published by the custody handler with the release.
+        for (msg, t) in tokens.iter() {
+            if *msg == ObjectStatus::Unwrapped {
+                continue;
+            }
+            state
+                .messages
+                .push(IndexingMessage::Token(((*msg).into(), t.0.clone())));
+        }
+
+        let (changed_tokens, tokens_act) = token_indexer_work(&tokens)?;
+        if changed_tokens.len() > 0 {
+            batch_change(conn, &changed_tokens)?;
+        }
+
+        for (_, (t, _)) in tokens.iter() {
+            token_attributes::replace(
+                conn,
+                t.chain_id,
+                &t.token_id,
+                t.version,
+                &attribute_rows(t),
+            )?;
+        }
+
+        let mut owners = tokens
+            .iter()
+            .map(|(_, (t, _))| Ownership {
+                chain_id: t.chain_id,
+                token_id: t.token_id.clone(),
+                owner_address: t.owner_address.clone(),
+                owner_kind: t.owner_kind,
+                from_version: t.version,
+                from_checkpoint: checkpoint.sequence_number as i64,
+                from_time: t.updated_at.unwrap_or_default(),
+                tx_digest: t.tx.clone(),
+            })
+            .collect::<Vec<Ownership>>();
+        if owners.len() > 0 {
+            owners.sort_by_key(|o| o.from_version);
+            token_ownership_history::batch_record(conn, &owners)?;
+        }
+
+        state.activities.extend(tokens_act);
+        state.tokens = tokens;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_exact_and_prefix_patterns() {
+        let nft_types = NftTypes::new(&vec![
+            "0x5::nft::Nft".to_string(),
+            "0000000000000000000000000000000000000000000000000000000000000006::art::*"
+                .to_string(),
+            " ".to_string(),
+        ]);
+        assert!(nft_types.matches("0x5::nft::Nft"));
+        assert!(!nft_types.matches("0x5::nft::NftCap"));
+        assert!(!nft_types.matches("0x5::nft::Other"));
+        assert!(nft_types.matches("0x6::art::Piece"));
+        assert!(!nft_types.matches("0x6::artwork::Piece"));
+        assert!(!nft_types.matches("0x7::art::Piece"));
+    }
+
+    #[test]
+    fn matches_nothing_without_patterns() {
+        let nft_types = NftTypes::new(&vec![]);
+        assert!(!nft_types.matches("0x5::nft::Nft"));
+        assert!(!nft_types.matches(""));
+    }
+}
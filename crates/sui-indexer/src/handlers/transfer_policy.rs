@@ -0,0 +1,412 @@
+use anyhow::{anyhow, Result};
+use diesel::PgConnection;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use sui_sdk::rpc_types::{Checkpoint, SuiObjectData, SuiParsedData};
+use sui_sdk::types::object::Owner;
+use tracing::{info, warn};
+
+use crate::handlers::{
+    failed_object, CheckpointHandler, CheckpointState, HandlerContext,
+};
+use crate::indexer::CheckpointData;
+use crate::models::collections::{self, Collection};
+use crate::models::failed_items::FailedItem;
+use crate::models::tokens::{self, Token};
+use crate::models::transfer_policies::{
+    self, TransferPolicy, TransferPolicyRule, ROYALTY_DENOMINATOR,
+};
+use crate::ObjectStatus;
+
+const POLICY: &str = "0x2::transfer_policy::TransferPolicy<";
+const POLICY_CAP: &str = "0x2::transfer_policy::TransferPolicyCap<";
+const RULE_FIELD: &str =
+    "0x2::dynamic_field::Field<0x2::transfer_policy::RuleKey<";
+
+const ROYALTY_RULE: &str = "::royalty_rule::Rule";
+const FLOOR_PRICE_RULE: &str = "::floor_price_rule::Rule";
+const KIOSK_LOCK_RULE: &str = "::kiosk_lock_rule::Rule";
+
+#[derive(Default)]
+struct PolicyChanges {
+    policies: Vec<TransferPolicy>,
+    rules: Vec<TransferPolicyRule>,
+    /// Policy id to the holder of its `TransferPolicyCap`.
+    caps: Vec<(String, String)>,
+}
+
+/// `TypeName`s are printed without the `0x` and with a zero padded
+/// address, object types with both trimmed.
+pub(crate) fn normalize_type(type_: &str) -> String {
+    let type_ = type_.trim_start_matches("0x");
+    match type_.split_once("::") {
+        Some((address, rest)) => {
+            format!("{}::{}", address.trim_start_matches('0'), rest)
+        }
+        None => type_.to_string(),
+    }
+}
+
+/// The first type parameter of a generic type, given what follows its `<`.
+fn first_type_param(params: &str) -> Option<&str> {
+    let mut depth = 0;
+    for (i, c) in params.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' if depth == 0 => return Some(&params[..i]),
+            '>' => depth -= 1,
+            ',' if depth == 0 => return Some(&params[..i]),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Move structs nested in the parsed content may keep their `fields`.
+fn struct_fields(value: &Value) -> &Value {
+    match value.get("fields") {
+        Some(fields) => fields,
+        None => value,
+    }
+}
+
+/// u64s come as strings, smaller integers as numbers.
+fn json_i64(value: &Value) -> Option<i64> {
+    value
+        .as_i64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+fn content(obj: &SuiObjectData) -> Result<Value> {
+    match obj.content.as_ref() {
+        Some(SuiParsedData::MoveObject(parse_obj)) => {
+            Ok(parse_obj.fields.clone().to_json_value())
+        }
+        _ => Err(anyhow!("object has no content")),
+    }
+}
+
+fn parse_policy(
+    obj: &SuiObjectData,
+    nft_type: &str,
+    chain_id: i64,
+    timestamp: u64,
+) -> Result<TransferPolicy> {
+    let fields = content(obj)?;
+    let rules = struct_fields(&fields["rules"])["contents"]
+        .as_array()
+        .ok_or_else(|| anyhow!("TransferPolicy without rules"))?
+        .iter()
+        .map(|rule| {
+            let rule = struct_fields(rule);
+            rule.as_str()
+                .or_else(|| rule["name"].as_str())
+                .map(normalize_type)
+                .ok_or_else(|| anyhow!("unexpected rule type {}", rule))
+        })
+        .collect::<Result<Vec<String>>>()?;
+
+    Ok(TransferPolicy {
+        chain_id,
+        nft_type: nft_type.to_string(),
+        policy_id: obj.object_id.to_string(),
+        rules: serde_json::to_string(&rules)?,
+        royalty_bp: None,
+        royalty_min_amount: None,
+        floor_price: None,
+        kiosk_lock: false,
+        payee_address: None,
+        version: obj.version.value() as i64,
+        created_at: timestamp as i64,
+        updated_at: timestamp as i64,
+    })
+}
+
+fn parse_rule(
+    obj: &SuiObjectData,
+    rule_type: &str,
+    chain_id: i64,
+    timestamp: u64,
+) -> Result<TransferPolicyRule> {
+    let policy_id = match obj.owner {
+        Some(Owner::ObjectOwner(parent)) => parent.to_string(),
+        _ => return Err(anyhow!("rule config is not owned by a policy")),
+    };
+    let fields = content(obj)?;
+
+    Ok(TransferPolicyRule {
+        chain_id,
+        policy_id,
+        rule_type: normalize_type(rule_type),
+        config: serde_json::to_string(struct_fields(&fields["value"]))?,
+        version: obj.version.value() as i64,
+        updated_at: timestamp as i64,
+    })
+}
+
+fn parse_cap(obj: &SuiObjectData) -> Result<(String, String)> {
+    let fields = content(obj)?;
+    let policy_id = fields["policy_id"]
+        .as_str()
+        .ok_or_else(|| anyhow!("TransferPolicyCap without a policy"))?;
+    let owner_address = obj
+        .owner
+        .as_ref()
+        .and_then(|owner| owner.get_owner_address().ok())
+        .ok_or_else(|| anyhow!("TransferPolicyCap is not address owned"))?;
+    Ok((policy_id.to_string(), owner_address.to_string()))
+}
+
+fn parse_policy_changes(
+    checkpoint: &Checkpoint,
+    object_changes: &Vec<(ObjectStatus, SuiObjectData, String, u64)>,
+    chain_id: i64,
+    failures: &mut Vec<FailedItem>,
+) -> PolicyChanges {
+    let mut changes = PolicyChanges::default();
+    for (status, obj, _, timestamp) in object_changes {
+        if *status == ObjectStatus::Deleted
+            || *status == ObjectStatus::Wrapped
+            || *status == ObjectStatus::UnwrappedThenDeleted
+        {
+            continue;
+        }
+        let object_type = match obj.type_.as_ref() {
+            Some(type_) => type_.to_string(),
+            None => continue,
+        };
+
+        let parsed = if let Some(params) = object_type.strip_prefix(POLICY) {
+            first_type_param(params)
+                .ok_or_else(|| anyhow!("malformed type {}", object_type))
+                .and_then(|nft_type| {
+                    parse_policy(obj, nft_type, chain_id, *timestamp)
+                })
+                .map(|policy| changes.policies.push(policy))
+        } else if let Some(params) = object_type.strip_prefix(RULE_FIELD) {
+            first_type_param(params)
+                .ok_or_else(|| anyhow!("malformed type {}", object_type))
+                .and_then(|rule_type| {
+                    parse_rule(obj, rule_type, chain_id, *timestamp)
+                })
+                .map(|rule| changes.rules.push(rule))
+        } else if object_type.starts_with(POLICY_CAP) {
+            parse_cap(obj).map(|cap| changes.caps.push(cap))
+        } else {
+            continue;
+        };
+
+        if let Err(e) = parsed {
+            warn!(object_id = %obj.object_id, "Failed to parse transfer policy object: {:#}", e);
+            failures.push(failed_object(chain_id, checkpoint, obj, &e));
+        }
+    }
+    changes
+}
+
+/// Recompute the royalty, floor price and kiosk lock columns from the
+/// configs of the rules the policy currently has.
+fn summarize(
+    policy: &mut TransferPolicy,
+    configs: &Vec<TransferPolicyRule>,
+) -> Result<()> {
+    let rules: Vec<String> = serde_json::from_str(&policy.rules)?;
+    let config = |suffix: &str| -> Option<Value> {
+        let rule_type = rules.iter().find(|r| r.ends_with(suffix))?;
+        configs
+            .iter()
+            .find(|c| &c.rule_type == rule_type)
+            .and_then(|c| serde_json::from_str(&c.config).ok())
+    };
+
+    let royalty = config(ROYALTY_RULE);
+    policy.royalty_bp =
+        royalty.as_ref().and_then(|c| json_i64(&c["amount_bp"]));
+    policy.royalty_min_amount =
+        royalty.as_ref().and_then(|c| json_i64(&c["min_amount"]));
+    policy.floor_price = config(FLOOR_PRICE_RULE).and_then(|c| {
+        json_i64(&c["floor_price"]).or_else(|| json_i64(&c["floor"]))
+    });
+    policy.kiosk_lock = rules.iter().any(|r| r.ends_with(KIOSK_LOCK_RULE));
+    Ok(())
+}
+
+/// Fill the royalty of tokens from their type's transfer policy.
+pub fn fill_token_royalties(
+    conn: &mut PgConnection,
+    chain_id: i64,
+    tokens: &mut Vec<(ObjectStatus, (Token, String))>,
+) -> Result<()> {
+    let types = tokens
+        .iter()
+        .map(|(_, (t, _))| t.collection_type.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let royalties = transfer_policies::query_royalties(conn, chain_id, types)?;
+    for (_, (token, _)) in tokens.iter_mut() {
+        if let Some((bp, payee)) = royalties.get(&token.collection_type) {
+            token.royalty_points_numerator = *bp;
+            token.royalty_points_denominator = ROYALTY_DENOMINATOR;
+            token.payee_address = payee.clone().unwrap_or_default();
+        }
+    }
+    Ok(())
+}
+
+/// Fill the royalty of collections from their type's transfer policy.
+pub fn fill_collection_royalties(
+    conn: &mut PgConnection,
+    chain_id: i64,
+    collections: &mut Vec<(ObjectStatus, Collection)>,
+) -> Result<()> {
+    let types = collections
+        .iter()
+        .map(|(_, c)| c.collection_type.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let royalties = transfer_policies::query_royalties(conn, chain_id, types)?;
+    for (_, collection) in collections.iter_mut() {
+        if let Some((bp, _)) = royalties.get(&collection.collection_type) {
+            collection.royaltie = Some(bp.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Indexes `TransferPolicy<T>` objects, their rule configs and the holder
+/// of their cap, and writes royalty changes through to the tokens and
+/// collections of `T`. Register it after the collection and token handlers
+/// so tokens created in the same checkpoint are updated too.
+pub struct TransferPolicyHandler;
+
+impl CheckpointHandler for TransferPolicyHandler {
+    fn name(&self) -> &str { "transfer_policy" }
+
+    fn handle(
+        &mut self,
+        ctx: &mut HandlerContext,
+        (checkpoint, _, object_changed, _): &CheckpointData,
+        state: &mut CheckpointState,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let chain_id = ctx.network.chain_id();
+        let changes = parse_policy_changes(
+            checkpoint,
+            object_changed,
+            chain_id,
+            &mut state.failures,
+        );
+
+        let touched = changes
+            .policies
+            .iter()
+            .map(|p| p.policy_id.clone())
+            .chain(changes.rules.iter().map(|r| r.policy_id.clone()))
+            .chain(changes.caps.iter().map(|(id, _)| id.clone()))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        if touched.is_empty() {
+            return Ok(());
+        }
+
+        let before: HashMap<String, (Option<i64>, Option<String>)> =
+            transfer_policies::query_by_policy_ids(
+                conn,
+                chain_id,
+                touched.clone(),
+            )?
+            .into_iter()
+            .map(|p| (p.nft_type, (p.royalty_bp, p.payee_address)))
+            .collect();
+
+        if changes.policies.len() > 0 {
+            transfer_policies::batch_change(conn, chain_id, &changes.policies)?;
+        }
+        if changes.rules.len() > 0 {
+            transfer_policies::batch_change_rules(
+                conn,
+                chain_id,
+                &changes.rules,
+            )?;
+        }
+        for (policy_id, payee) in changes.caps.iter() {
+            transfer_policies::set_payee(conn, chain_id, policy_id, payee)?;
+        }
+
+        for mut policy in
+            transfer_policies::query_by_policy_ids(conn, chain_id, touched)?
+        {
+            let configs = transfer_policies::query_rules(
+                conn,
+                chain_id,
+                &policy.policy_id,
+            )?;
+            summarize(&mut policy, &configs)?;
+            transfer_policies::update_summary(conn, &policy)?;
+
+            // the policy object changes on every royalty payment, only
+            // rewrite the tokens when the terms did.
+            let royalty = (policy.royalty_bp, policy.payee_address.clone());
+            if before.get(&policy.nft_type) == Some(&royalty) {
+                continue;
+            }
+            info!(nft_type = %policy.nft_type, royalty_bp = ?policy.royalty_bp, "Transfer policy royalty changed");
+            tokens::update_royalty(
+                conn,
+                chain_id,
+                &policy.nft_type,
+                policy.royalty_bp.unwrap_or_default(),
+                policy.royalty_bp.map_or(0, |_| ROYALTY_DENOMINATOR),
+                &policy.payee_address.unwrap_or_default(),
+            )?;
+            collections::update_royalty(
+                conn,
+                chain_id,
+                &policy.nft_type,
+                policy.royalty_bp.map(|bp| bp.to_string()),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_the_type_address() {
+        assert_eq!(
+            normalize_type(
+                "0x0000000000000000000000000000000000000000000000000000000000000002::sui::SUI"
+            ),
+            "2::sui::SUI"
+        );
+        assert_eq!(
+            normalize_type(
+                "0000000000000000000000000000000000000000000000000000000000000abc::nft::Nft"
+            ),
+            "abc::nft::Nft"
+        );
+        assert_eq!(normalize_type("0xabc::nft::Nft"), "abc::nft::Nft");
+        // type parameters are kept as written.
+        assert_eq!(
+            normalize_type("0x2::coin::Coin<0x0002::sui::SUI>"),
+            "2::coin::Coin<0x0002::sui::SUI>"
+        );
+        assert_eq!(normalize_type("u64"), "u64");
+    }
+
+    #[test]
+    fn finds_the_first_type_param() {
+        assert_eq!(first_type_param("0x2::sui::SUI>"), Some("0x2::sui::SUI"));
+        assert_eq!(
+            first_type_param("0x5::a::B<0x2::c::D, u8>, u64>"),
+            Some("0x5::a::B<0x2::c::D, u8>")
+        );
+        assert_eq!(first_type_param("0x2::sui::SUI"), None);
+    }
+}
@@ -1,27 +1,46 @@
 pub mod receiver;
+pub mod shutdown;
+pub mod source;
 
-use anyhow::{Error, Result};
+use anyhow::{bail, Error, Result};
 use diesel::pg::PgConnection;
-use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use diesel::ExpressionMethods;
 use diesel::QueryDsl;
 use diesel::RunQueryDsl;
 use futures::future::join_all;
 
-use futures::StreamExt;
 use redis::Commands;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use sui_sdk::types::messages_checkpoint::CheckpointSequenceNumber;
 use sui_sdk::SuiClient;
+
+use self::shutdown::Shutdown;
+use self::source::{CheckpointSource, MissingCheckpoint};
 use tokio::sync::mpsc::Sender;
 
-use crate::handlers::event::{event_handle, EventAccount};
-use crate::models::activities::batch_insert as batch_insert_activities;
+use crate::handlers::activity::ActivityHandler;
+use crate::handlers::burn::BurnHandler;
+use crate::handlers::coin::CoinHandler;
+use crate::handlers::collection::CollectionHandler;
+use crate::handlers::custody::CustodyHandler;
+use crate::handlers::event::{EventAccount, MarketplaceHandler};
+use crate::handlers::expiry::ExpiryHandler;
+use crate::handlers::kiosk::KioskHandler;
+use crate::handlers::supply::SupplyHandler;
+use crate::handlers::token::{NftTypes, TokenHandler};
+use crate::handlers::transfer_policy::TransferPolicyHandler;
+use crate::handlers::{
+    CheckpointHandler, CheckpointState, HandlerContext, HandlerRegistry,
+};
 use crate::models::check_point::query_check_point;
-use crate::models::collections::batch_insert;
+use crate::models::collections::query_collection_types;
+use crate::models::failed_items;
+use crate::models::marketplace_packages;
 
-use crate::models::tokens::batch_change;
 use crate::{
     fetch_changed_objects, get_deleted_db_objects, get_object_changes,
     multi_get_full_transactions, ObjectStatus,
@@ -31,13 +50,10 @@ use sui_sdk::rpc_types::{
     Checkpoint, SuiEvent, SuiObjectData, SuiTransactionBlockResponse,
 };
 
+use crate::archive::CheckpointArchive;
 use crate::config::Config;
-use crate::handlers::activity::parse_tokens_activity;
-use crate::handlers::collection::{collection_indexer_work, parse_collection};
-use crate::handlers::event::parse_event;
-use crate::handlers::token::{parse_tokens, token_indexer_work};
 use crate::indexer::receiver::IndexingMessage;
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 
 extern crate redis;
 
@@ -45,18 +61,27 @@ use crate::schema::check_point::dsl::check_point;
 use crate::schema::check_point::{chain_id, version};
 use crate::MULTI_GET_CHUNK_SIZE;
 
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
 #[derive(Clone)]
 pub(crate) struct Indexer {
     config: Config,
-    sui_client: SuiClient,
+    source: Arc<dyn CheckpointSource>,
     postgres: Pool<ConnectionManager<PgConnection>>,
     redis: redis::Client,
     sender: Sender<IndexingMessage>,
-    check_point_data_sender: flume::Sender<Vec<CheckpointData>>,
-    check_point_data_receiver: flume::Receiver<Vec<CheckpointData>>,
+    archive: Option<Arc<Mutex<CheckpointArchive>>>,
 }
 
-type CheckpointData = (
+struct Writer {
+    pg: PooledConnection<ConnectionManager<PgConnection>>,
+    ctx: HandlerContext,
+    registry: HandlerRegistry,
+}
+
+pub type CheckpointData = (
     Checkpoint,
     Vec<SuiTransactionBlockResponse>,
     Vec<(ObjectStatus, SuiObjectData, String, u64)>,
@@ -66,189 +91,458 @@ type CheckpointData = (
 impl Indexer {
     pub fn new(
         config: Config,
-        sui_client: SuiClient,
+        source: Arc<dyn CheckpointSource>,
         postgres: Pool<ConnectionManager<PgConnection>>,
         redis: redis::Client,
         sender: Sender<IndexingMessage>,
+        archive: Option<Arc<Mutex<CheckpointArchive>>>,
         //        algo: algoliasearch::Client,
     ) -> Self {
-        let (s, r) = flume::unbounded::<Vec<CheckpointData>>();
-
         Self {
             config,
-            sui_client,
+            source,
             postgres,
             redis,
             sender,
-            check_point_data_sender: s,
-            check_point_data_receiver: r,
+            archive,
         }
     }
 
-    pub async fn run_forever(&mut self) -> Result<()> {
+    /// Download checkpoints into `batches` until shutdown is requested.
+    /// Returning drops `batches`, which lets the writer drain and stop.
+    pub async fn run_forever(
+        &mut self,
+        batches: flume::Sender<Vec<CheckpointData>>,
+        mut shutdown: Shutdown,
+    ) -> Result<()> {
         let mut pg = self.postgres.get()?;
-        let mut indexer = query_check_point(&mut pg, 1)? as u64;
+        let mut indexer =
+            query_check_point(&mut pg, self.config.network.chain_id())? as u64;
 
-        let batch_index = self.config.batch_index;
+        let max_window = self.config.batch_index.max(1);
+        let mut window = max_window;
+        let mut backoff = INITIAL_BACKOFF;
 
-        let last_sequence = self
-            .sui_client
-            .read_api()
-            .get_latest_checkpoint_sequence_number()
-            .await?;
+        let last_sequence = self.source.latest_sequence_number().await?;
 
         info!(
-            "Start indexer Worker at: {} Fullnode sequence number: {}",
-            indexer, last_sequence
+            "Start indexer Worker at: {} {} sequence number: {}",
+            indexer,
+            self.source.name(),
+            last_sequence
         );
 
-        loop {
-            let download_futures = (indexer..(indexer + batch_index))
-                .map(|x| download_checkpoint_data(&self.sui_client, x));
-
-            let download_results = join_all(download_futures).await;
-            let mut downloaded_checkpoints = vec![];
-
-            for download_result in download_results {
-                if let Ok(checkpoint) = download_result {
-                    downloaded_checkpoints.push(checkpoint);
-                } else {
-                    if let Err(fn_e) = download_result {
-                        warn!(
-                            "Unexpected response from fullnode for checkpoints: {}",
-                            fn_e
-                        );
-                    }
-                    break;
+        while !shutdown.requested() {
+            let latest = match self.source.latest_sequence_number().await {
+                Ok(latest) => latest,
+                Err(e) => {
+                    warn!(
+                        backoff_ms = backoff.as_millis() as u64,
+                        "Failed to fetch latest checkpoint: {}", e
+                    );
+                    shutdown.sleep(backoff).await;
+                    backoff = next_backoff(backoff);
+                    continue;
                 }
+            };
+
+            if indexer > latest {
+                // caught up with the source, wait for a new checkpoint.
+                shutdown.sleep(POLL_INTERVAL).await;
+                continue;
             }
 
+            window = adjust_window(window, latest - indexer + 1, max_window);
+
+            let started = Instant::now();
+            let downloaded_checkpoints = tokio::select! {
+                batch = self.download_batch(indexer, window) => batch?,
+                _ = shutdown.wait() => break,
+            };
+
             if downloaded_checkpoints.is_empty() {
                 warn!(
+                    backoff_ms = backoff.as_millis() as u64,
                     "No checkpoints were downloaded for sequence number {}, retrying...",
                     indexer
                 );
+                window = (window / 2).max(1);
+                shutdown.sleep(backoff).await;
+                backoff = next_backoff(backoff);
                 continue;
             }
+            backoff = INITIAL_BACKOFF;
 
-            self.check_point_data_sender
-                .send_async(downloaded_checkpoints.clone())
-                .await?;
+            let downloaded = downloaded_checkpoints.len() as u64;
+            if downloaded < window {
+                window = downloaded;
+            }
+            let download_elapsed = started.elapsed();
+
+            // blocks while the writer is `queue_capacity` batches behind.
+            tokio::select! {
+                sent = batches.send_async(downloaded_checkpoints) => sent?,
+                _ = shutdown.wait() => {
+                    info!(
+                        abandoned_check_points = downloaded,
+                        "Shutdown while the queue was full, dropping batch"
+                    );
+                    break;
+                }
+            }
 
-            indexer += downloaded_checkpoints.len() as u64;
+            indexer += downloaded;
 
             info!(
-                download_check_points = downloaded_checkpoints.len(),
+                download_check_points = downloaded,
                 next_sequence_start = indexer,
+                behind = latest.saturating_sub(indexer),
+                window,
+                queue_depth = batches.len(),
+                checkpoints_per_sec = per_second(downloaded, download_elapsed),
+                blocked_ms =
+                    (started.elapsed() - download_elapsed).as_millis() as u64,
                 "transactions processed"
             );
         }
+
+        info!(next_sequence_start = indexer, "Downloader stopped");
+        Ok(())
     }
 
-    pub async fn handle_check_points(&mut self) -> Result<()> {
-        let mut receiver = self.check_point_data_receiver.clone().into_stream();
-        let mut pg = self.postgres.get()?;
-        let mut redis = self.redis.get_connection()?;
-        let mut collects_set: HashMap<String, String> =
-            redis.hgetall("collections")?;
+    /// Re-index the closed range `from..=to` through the same download and
+    /// handler chain as `run_forever`, leaving the live `check_point` row
+    /// untouched so it can run next to the live indexer. On shutdown it
+    /// stops after the checkpoint being stored.
+    pub async fn backfill(
+        &mut self,
+        from: u64,
+        to: u64,
+        extra: Vec<Box<dyn CheckpointHandler>>,
+        mut shutdown: Shutdown,
+    ) -> Result<()> {
+        if from > to {
+            bail!("Backfill range is empty: from {} is after to {}", from, to);
+        }
 
-        let event_account = EventAccount::new(
-            self.config.bob_yard.clone(),
-            self.config.origin_byte.clone(),
-        );
+        let mut writer = self.writer(extra)?;
+        let max_window = self.config.batch_index.max(1);
+        let mut window = max_window;
+        let mut backoff = INITIAL_BACKOFF;
+        let mut next = from;
 
-        while let Some(downloaded_checkpoints) = receiver.next().await {
-            for (check_point_data, _, object_changed, events) in
-                downloaded_checkpoints
-            {
-                let collections = parse_collection(
-                    &object_changed,
-                    &mut redis,
-                    &mut collects_set,
-                )?;
-
-                let tokens = parse_tokens(&object_changed, &mut collects_set)?;
-                let events = parse_event(&events, &event_account)?;
-                let mut activities = parse_tokens_activity(&events, &tokens);
-
-                for (msg, collection) in collections.iter() {
-                    self.sender
-                        .send(IndexingMessage::Collection((
-                            (*msg).into(),
-                            collection.clone(),
-                        )))
-                        .await?;
-                }
+        info!(from, to, "Start backfill");
+
+        while next <= to && !shutdown.requested() {
+            window = adjust_window(window, to - next + 1, max_window);
 
-                for (msg, t) in tokens.iter() {
-                    self.sender
-                        .send(IndexingMessage::Token((
-                            (*msg).into(),
-                            t.0.clone(),
-                        )))
-                        .await?;
+            let started = Instant::now();
+            let downloaded_checkpoints = tokio::select! {
+                batch = self.download_batch(next, window) => batch?,
+                _ = shutdown.wait() => break,
+            };
+            if downloaded_checkpoints.is_empty() {
+                warn!(
+                    backoff_ms = backoff.as_millis() as u64,
+                    "No checkpoints were downloaded for sequence number {}, retrying...",
+                    next
+                );
+                window = (window / 2).max(1);
+                shutdown.sleep(backoff).await;
+                backoff = next_backoff(backoff);
+                continue;
+            }
+            backoff = INITIAL_BACKOFF;
+
+            let mut downloaded = 0;
+            for data in downloaded_checkpoints {
+                if shutdown.requested() {
+                    break;
                 }
+                self.store_check_point(&mut writer, data, false).await?;
+                downloaded += 1;
+            }
+            next += downloaded;
 
-                let (collections, collect_act) =
-                    collection_indexer_work(&collections)?;
+            info!(
+                backfill_check_points = downloaded,
+                next_sequence_start = next,
+                remaining = (to + 1).saturating_sub(next),
+                checkpoints_per_sec = per_second(downloaded, started.elapsed()),
+                "backfill processed"
+            );
+        }
 
-                activities.extend_from_slice(&collect_act);
-                let (tokens, tokens_act) = token_indexer_work(&tokens)?;
-                activities.extend_from_slice(&tokens_act);
+        if next <= to {
+            warn!(
+                from,
+                to,
+                next_sequence_start = next,
+                "Backfill interrupted, resume with --from {}",
+                next
+            );
+        } else {
+            info!(from, to, "Backfill finished");
+        }
+        Ok(())
+    }
 
-                pg.build_transaction().read_write().run(|conn| {
-                    if collections.len() > 0 {
-                        batch_insert(conn, &collections).unwrap();
-                    }
+    /// Re-index every checkpoint that still has open `failed_items` with
+    /// the current parsers, leaving the live cursor untouched.
+    pub async fn retry_failed(
+        &mut self,
+        extra: Vec<Box<dyn CheckpointHandler>>,
+        shutdown: Shutdown,
+    ) -> Result<()> {
+        let network = self.config.network;
+        let mut writer = self.writer(extra)?;
+        let checkpoints = failed_items::query_unresolved_checkpoints(
+            &mut writer.pg,
+            network.chain_id(),
+        )?;
+
+        info!(checkpoints = checkpoints.len(), "Retrying failed items");
+
+        for sequence_number in checkpoints {
+            if shutdown.requested() {
+                break;
+            }
+            let data = self.source.fetch(sequence_number as u64).await?;
+            self.store_check_point(&mut writer, data, false).await?;
+        }
 
-                    if tokens.len() > 0 {
-                        batch_change(conn, &tokens).unwrap();
-                    }
+        let remaining =
+            failed_items::count_unresolved(&mut writer.pg, network.chain_id())?;
+        if remaining > 0 {
+            warn!(remaining, "Failed items are still failing");
+        } else {
+            info!("All failed items resolved");
+        }
+        Ok(())
+    }
 
-                    if events.len() > 0 {
-                        event_handle(
-                            &events,
-                            check_point_data.timestamp_ms as i64,
-                            conn,
-                        )
-                        .unwrap();
-                    }
+    /// Store the batches sent by `run_forever` until the channel closes.
+    /// Once shutdown is requested the checkpoint being stored still commits
+    /// with its cursor, and the queued ones are abandoned to be downloaded
+    /// again on the next start.
+    pub async fn handle_check_points(
+        &mut self,
+        batches: flume::Receiver<Vec<CheckpointData>>,
+        extra: Vec<Box<dyn CheckpointHandler>>,
+        shutdown: Shutdown,
+    ) -> Result<()> {
+        let mut writer = self.writer(extra)?;
+        let mut next_sequence = None;
+        let mut abandoned = 0;
+
+        while let Ok(downloaded_checkpoints) = batches.recv_async().await {
+            let started = Instant::now();
+            let mut stored = 0;
+
+            for data in downloaded_checkpoints {
+                if shutdown.requested() {
+                    abandoned += 1;
+                    continue;
+                }
+                let sequence_number = data.0.sequence_number;
+                self.store_check_point(&mut writer, data, true).await?;
+                next_sequence = Some(sequence_number + 1);
+                stored += 1;
+            }
 
-                    if activities.len() > 0 {
-                        batch_insert_activities(conn, &activities).unwrap();
-                    }
+            if stored > 0 {
+                info!(
+                    stored_check_points = stored,
+                    queue_depth = batches.len(),
+                    checkpoints_per_sec = per_second(stored, started.elapsed()),
+                    "checkpoints stored"
+                );
+            }
+        }
 
-                    let updated_row = diesel::update(
-                        check_point.filter(chain_id.eq(1)),
-                    )
-                    .set(version.eq(check_point_data.sequence_number as i64))
-                    .get_result::<(i64, i64)>(conn);
-                    assert_eq!(
-                        Ok((1, check_point_data.sequence_number as i64)),
-                        updated_row
-                    );
+        info!(
+            next_sequence_start = ?next_sequence,
+            abandoned_check_points = abandoned,
+            "Writer stopped"
+        );
+        Ok(())
+    }
+
+    /// Build the handler chain: the built-in kiosk, collection, token, burn,
+    /// custody, supply, transfer policy, coin, marketplace and expiry
+    /// handlers, then `extra`, then the activity handler that stores the
+    /// activities all of them produced.
+    fn writer(&self, extra: Vec<Box<dyn CheckpointHandler>>) -> Result<Writer> {
+        let network = self.config.network;
+        let mut pg = self.postgres.get()?;
+        let mut redis = self.redis.get_connection()?;
+        let mut collection_types: HashMap<String, String> =
+            redis.hgetall(network.redis_key("collections"))?;
+
+        // fill whatever the Redis namespace is missing from Postgres.
+        for (collection_type, collection_id) in
+            query_collection_types(&mut pg, network.chain_id())?
+        {
+            collection_types
+                .entry(collection_type)
+                .or_insert(collection_id);
+        }
 
-                    /*                     info!(
-                        sequence_number = check_point_data.sequence_number,
-                        "indexer store success processed"
-                    ); */
+        let mut event_account = EventAccount::new(&self.config)?;
+        event_account.extend(marketplace_packages::query_packages(
+            &mut pg,
+            network.chain_id(),
+        )?);
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Box::new(KioskHandler));
+        registry.register(Box::new(CollectionHandler::new(redis)));
+        registry.register(Box::new(TokenHandler::new(NftTypes::new(
+            &self.config.nft_types,
+        ))));
+        registry.register(Box::new(BurnHandler));
+        registry.register(Box::new(CustodyHandler));
+        registry.register(Box::new(SupplyHandler));
+        registry.register(Box::new(TransferPolicyHandler));
+        registry.register(Box::new(CoinHandler));
+        registry.register(Box::new(MarketplaceHandler::new(event_account)));
+        registry.register(Box::new(ExpiryHandler::new(
+            self.config.expiry_sweep_ms,
+        )));
+        for handler in extra {
+            registry.register(handler);
+        }
+        registry.register(Box::new(ActivityHandler));
+        info!(handlers = ?registry.names(), "Checkpoint handlers registered");
+
+        Ok(Writer {
+            pg,
+            ctx: HandlerContext {
+                network,
+                collection_types,
+            },
+            registry,
+        })
+    }
 
-                    Ok::<(), anyhow::Error>(())
-                })?;
+    /// Run the handler chain over one checkpoint in a single transaction and
+    /// publish its messages once committed. The live cursor is only moved
+    /// when `update_cursor` is set.
+    async fn store_check_point(
+        &self,
+        writer: &mut Writer,
+        data: CheckpointData,
+        update_cursor: bool,
+    ) -> Result<()> {
+        let network = self.config.network;
+        let Writer { pg, ctx, registry } = writer;
+
+        let sequence_number = data.0.sequence_number as i64;
+        let state = pg.build_transaction().read_write().run(|conn| {
+            let state = registry.handle(ctx, &data, conn)?;
+
+            // a replay only keeps the failures that still happen.
+            failed_items::resolve_checkpoint(
+                conn,
+                network.chain_id(),
+                sequence_number,
+            )?;
+            if state.failures.len() > 0 {
+                failed_items::batch_upsert(conn, &state.failures)?;
             }
+
+            if update_cursor {
+                let next_sequence = sequence_number + 1;
+                let updated_row = diesel::update(
+                    check_point.filter(chain_id.eq(network.chain_id())),
+                )
+                .set(version.eq(next_sequence))
+                .get_result::<(i64, i64)>(conn);
+                assert_eq!(
+                    Ok((network.chain_id(), next_sequence)),
+                    updated_row
+                );
+            }
+
+            Ok::<CheckpointState, anyhow::Error>(state)
+        })?;
+
+        if state.failures.len() > 0 {
+            warn!(
+                sequence_number,
+                failed_items = state.failures.len(),
+                "Checkpoint stored with dead-lettered items"
+            );
         }
 
+        for msg in state.messages {
+            self.sender.send(msg).await?;
+        }
         Ok(())
     }
 
-    // pub async fn shut_down(&self) {
-    //     info!("Shutting down the index-workers...");
-    //     // Abort the tasks.
-    //
-    //     //self.handles.lock().iter().for_each(|handle| handle.abort());
-    //
-    //     //self.tcp.shut_down().await;
-    // }
+    /// Download up to `window` checkpoints starting at `start`, returning
+    /// the contiguous prefix that succeeded. Fails when `start` is missing
+    /// from the source for good.
+    async fn download_batch(
+        &self,
+        start: CheckpointSequenceNumber,
+        window: u64,
+    ) -> Result<Vec<CheckpointData>> {
+        let download_futures =
+            (start..(start + window)).map(|x| self.source.fetch(x));
+
+        let download_results = join_all(download_futures).await;
+        let mut downloaded_checkpoints = vec![];
+
+        for download_result in download_results {
+            match download_result {
+                Ok(checkpoint) => downloaded_checkpoints.push(checkpoint),
+                Err(fn_e) => {
+                    if downloaded_checkpoints.is_empty()
+                        && fn_e.is::<MissingCheckpoint>()
+                    {
+                        return Err(fn_e);
+                    }
+                    warn!(
+                        "Unexpected response from {} for checkpoints: {}",
+                        self.source.name(),
+                        fn_e
+                    );
+                    break;
+                }
+            }
+        }
+
+        if let Some(archive) = &self.archive {
+            let mut archive = archive.lock().expect("archive lock poisoned");
+            for data in downloaded_checkpoints.iter() {
+                if let Err(e) = archive.write(data) {
+                    error!(
+                        sequence_number = data.0.sequence_number,
+                        "Failed to archive checkpoint: {}", e
+                    );
+                }
+            }
+        }
+
+        Ok(downloaded_checkpoints)
+    }
+}
+
+fn next_backoff(backoff: Duration) -> Duration {
+    (backoff * 2).min(MAX_BACKOFF)
+}
+
+/// Grow the download window exponentially while far behind the source and
+/// never ask for more checkpoints than exist.
+fn adjust_window(window: u64, distance: u64, max_window: u64) -> u64 {
+    (window * 2).min(max_window).min(distance).max(1)
+}
+
+fn per_second(count: u64, elapsed: Duration) -> u64 {
+    let millis = elapsed.as_millis().max(1) as u64;
+    count * 1000 / millis
 }
 
 async fn download_checkpoint_data(
@@ -327,3 +621,25 @@ async fn download_checkpoint_data(
 
     Ok((checkpoint, transactions, changed_objects, events))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_doubles_up_to_the_bounds() {
+        assert_eq!(adjust_window(1, 1000, 64), 2);
+        assert_eq!(adjust_window(32, 1000, 64), 64);
+        assert_eq!(adjust_window(64, 1000, 64), 64);
+        // never more than the checkpoints left, never empty.
+        assert_eq!(adjust_window(8, 3, 64), 3);
+        assert_eq!(adjust_window(8, 0, 64), 1);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_max() {
+        assert_eq!(next_backoff(INITIAL_BACKOFF), INITIAL_BACKOFF * 2);
+        assert_eq!(next_backoff(Duration::from_secs(20)), MAX_BACKOFF);
+        assert_eq!(next_backoff(MAX_BACKOFF), MAX_BACKOFF);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::config::Network;
+use crate::handlers::expiry::Expired;
 use crate::models::collections::Collection;
 use crate::models::tokens::Token;
 use crate::ObjectStatus;
@@ -49,21 +51,29 @@ impl Message {
 pub enum IndexingMessage {
     Collection((Message, Collection)),
     Token((Message, Token)),
+    Expired(Expired),
 }
 
 pub struct IndexSender {
     receiver: Receiver<IndexingMessage>,
     rabbitmq: Connection,
+    network: Network,
 }
 
 pub const TOKEN_EXCHANGE: &str = "token";
 pub const COLLECTION_EXCHANGE: &str = "collection";
+pub const MARKET_EXCHANGE: &str = "market";
 
 impl IndexSender {
-    pub fn new(receiver: Receiver<IndexingMessage>, conn: Connection) -> Self {
+    pub fn new(
+        receiver: Receiver<IndexingMessage>,
+        conn: Connection,
+        network: Network,
+    ) -> Self {
         Self {
             receiver,
             rabbitmq: conn,
+            network,
         }
     }
 
@@ -78,14 +88,21 @@ impl IndexSender {
 
         while let Some(msg) = self.receiver.recv().await {
             match msg {
-                IndexingMessage::Collection((_, collection)) => {
+                IndexingMessage::Collection((message, collection)) => {
                     let payload = serde_json::to_vec(&collection)
                         .expect("send collection to json failed")
                         .clone();
+                    // new collections keep the bare key consumers bind to.
+                    let rk = match message {
+                        Message::Update => {
+                            self.network.routing_key("collection.update")
+                        }
+                        _ => self.network.routing_key("collection"),
+                    };
                     channel
                         .basic_publish(
-                            "collection",
-                            "collection",
+                            COLLECTION_EXCHANGE,
+                            &rk,
                             BasicPublishOptions::default(),
                             &payload,
                             BasicProperties::default(),
@@ -96,7 +113,9 @@ impl IndexSender {
                     let payload = serde_json::to_vec(&token)
                         .expect("send collection to json failed")
                         .clone();
-                    let rk = format!("token.{}", message.to_str());
+                    let rk = self
+                        .network
+                        .routing_key(&format!("token.{}", message.to_str()));
 
                     channel
                         .basic_publish(
@@ -108,9 +127,31 @@ impl IndexSender {
                         )
                         .await?;
                 }
+                IndexingMessage::Expired(expired) => {
+                    let payload = serde_json::to_vec(&expired)
+                        .expect("send expired to json failed");
+                    let rk = self.network.routing_key(&format!(
+                        "{}.expire",
+                        expired.kind.to_str()
+                    ));
+
+                    channel
+                        .basic_publish(
+                            MARKET_EXCHANGE,
+                            &rk,
+                            BasicPublishOptions::default(),
+                            &payload,
+                            BasicProperties::default(),
+                        )
+                        .await?;
+                }
             }
         }
 
+        // every sender is gone, close so pending publishes are flushed.
+        channel.close(200, "indexer shutting down").await?;
+        self.rabbitmq.close(200, "indexer shutting down").await?;
+        info!("RabbitMQ sender flushed");
         Ok(())
     }
 }
@@ -138,5 +179,16 @@ pub async fn create_exchange(channel: lapin::Channel) -> Result<()> {
             FieldTable::default(),
         )
         .await?;
+
+    let mut opt = ExchangeDeclareOptions::default();
+    opt.durable = true;
+    let _ = channel
+        .exchange_declare(
+            MARKET_EXCHANGE,
+            ExchangeKind::Topic,
+            opt,
+            FieldTable::default(),
+        )
+        .await?;
     Ok(())
 }
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tracing::warn;
+
+/// Set once SIGINT or SIGTERM was received, shared by every task of the
+/// pipeline.
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    /// Start listening for SIGINT and SIGTERM.
+    pub fn listen() -> Result<Self> {
+        let (tx, rx) = watch::channel(false);
+        let mut terminate = signal(SignalKind::terminate())?;
+
+        tokio::spawn(async move {
+            let name = tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = terminate.recv() => "SIGTERM",
+            };
+            warn!(signal = name, "Shutdown requested, draining the indexer");
+            let _ = tx.send(true);
+        });
+
+        Ok(Self(rx))
+    }
+
+    pub fn requested(&self) -> bool { *self.0.borrow() }
+
+    /// Resolves once shutdown was requested.
+    pub async fn wait(&mut self) {
+        while !self.requested() {
+            if self.0.changed().await.is_err() {
+                // the listener went away without a signal.
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Sleep for `duration`, returning early on shutdown.
+    pub async fn sleep(&mut self, duration: Duration) {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {}
+            _ = self.wait() => {}
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+use sui_sdk::types::messages_checkpoint::CheckpointSequenceNumber;
+use sui_sdk::SuiClient;
+
+use super::{download_checkpoint_data, CheckpointData};
+use crate::archive::{ArchiveEntry, CheckpointArchive};
+
+/// Where the indexer reads checkpoints from.
+pub trait CheckpointSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Highest checkpoint the source can currently serve.
+    fn latest_sequence_number(
+        &self,
+    ) -> BoxFuture<'_, Result<CheckpointSequenceNumber>>;
+
+    fn fetch(
+        &self,
+        seq: CheckpointSequenceNumber,
+    ) -> BoxFuture<'_, Result<CheckpointData>>;
+}
+
+/// A checkpoint the source will never serve, so retrying cannot help.
+#[derive(Debug)]
+pub struct MissingCheckpoint(pub CheckpointSequenceNumber);
+
+impl fmt::Display for MissingCheckpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Checkpoint {} is missing from the source", self.0)
+    }
+}
+
+impl std::error::Error for MissingCheckpoint {}
+
+/// Downloads checkpoints, transactions and past objects from a fullnode.
+pub struct FullnodeSource {
+    sui_client: SuiClient,
+}
+
+impl FullnodeSource {
+    pub fn new(sui_client: SuiClient) -> Self { Self { sui_client } }
+}
+
+impl CheckpointSource for FullnodeSource {
+    fn name(&self) -> &str { "fullnode" }
+
+    fn latest_sequence_number(
+        &self,
+    ) -> BoxFuture<'_, Result<CheckpointSequenceNumber>> {
+        async move {
+            Ok(self
+                .sui_client
+                .read_api()
+                .get_latest_checkpoint_sequence_number()
+                .await?)
+        }
+        .boxed()
+    }
+
+    fn fetch(
+        &self,
+        seq: CheckpointSequenceNumber,
+    ) -> BoxFuture<'_, Result<CheckpointData>> {
+        download_checkpoint_data(&self.sui_client, seq).boxed()
+    }
+}
+
+/// Replays checkpoints stored by `--archive-dir`. The index is re-read
+/// whenever it grows, so an archive that is still being written can be
+/// followed; a gap below its highest checkpoint fails with
+/// `MissingCheckpoint`.
+pub struct ArchiveSource {
+    archive: CheckpointArchive,
+    index: Mutex<(u64, BTreeMap<u64, ArchiveEntry>)>,
+}
+
+impl ArchiveSource {
+    pub fn new(archive: CheckpointArchive) -> Self {
+        Self {
+            archive,
+            index: Mutex::new((0, BTreeMap::new())),
+        }
+    }
+
+    fn refresh(
+        &self,
+    ) -> Result<MutexGuard<'_, (u64, BTreeMap<u64, ArchiveEntry>)>> {
+        let mut index = self.index.lock().expect("archive lock poisoned");
+        let len = self.archive.index_len()?;
+        if len != index.0 {
+            *index = (len, self.archive.entries()?);
+        }
+        Ok(index)
+    }
+}
+
+impl CheckpointSource for ArchiveSource {
+    fn name(&self) -> &str { "archive" }
+
+    fn latest_sequence_number(
+        &self,
+    ) -> BoxFuture<'_, Result<CheckpointSequenceNumber>> {
+        async move {
+            self.refresh()?
+                .1
+                .keys()
+                .next_back()
+                .copied()
+                .ok_or_else(|| anyhow!("Archive is empty"))
+        }
+        .boxed()
+    }
+
+    fn fetch(
+        &self,
+        seq: CheckpointSequenceNumber,
+    ) -> BoxFuture<'_, Result<CheckpointData>> {
+        async move {
+            let entry = {
+                let index = self.refresh()?;
+                match index.1.get(&seq) {
+                    Some(entry) => entry.clone(),
+                    None if index.1.keys().next_back() > Some(&seq) => {
+                        return Err(MissingCheckpoint(seq).into())
+                    }
+                    None => {
+                        return Err(anyhow!(
+                            "Checkpoint {} is not archived",
+                            seq
+                        ))
+                    }
+                }
+            };
+            self.archive.read(&entry)
+        }
+        .boxed()
+    }
+}
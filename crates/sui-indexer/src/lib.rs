@@ -1,3 +1,4 @@
+pub mod archive;
 pub mod config;
 pub mod handlers;
 pub mod indexer;
@@ -6,13 +7,21 @@ pub mod schema;
 pub mod utils;
 
 use anyhow::{anyhow, Error, Result};
-use config::Config;
+use archive::CheckpointArchive;
+use config::{Command, Config};
 use diesel::pg::PgConnection;
 use diesel::r2d2::ConnectionManager;
+use diesel::Connection;
 use futures::future::join_all;
 use futures::FutureExt;
+use handlers::CheckpointHandler;
+use indexer::shutdown::Shutdown;
+use indexer::source::{ArchiveSource, CheckpointSource, FullnodeSource};
+use indexer::CheckpointData;
 use indexer::Indexer;
 use lapin::ConnectionProperties;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
 use sui_sdk::apis::ReadApi;
 use sui_sdk::rpc_types::SuiTransactionBlockData::V1;
 use sui_sdk::rpc_types::{
@@ -28,7 +37,7 @@ use sui_sdk::types::base_types::{ObjectID, SequenceNumber};
 
 const MULTI_GET_CHUNK_SIZE: usize = 500;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ObjectStatus {
     Created,
     Mutated,
@@ -39,10 +48,57 @@ pub enum ObjectStatus {
 }
 
 pub async fn run(cfg: Config) -> Result<()> {
-    let sui = SuiClientBuilder::default()
-        .build(&cfg.node)
-        .await
-        .map_err(|e| anyhow!("Fullnode: {e}"))?;
+    run_with_handlers(cfg, vec![]).await
+}
+
+/// Same as `run`, with `extra` handlers registered after the built-in
+/// marketplace handler and before activities are stored.
+pub async fn run_with_handlers(
+    cfg: Config,
+    extra: Vec<Box<dyn CheckpointHandler>>,
+) -> Result<()> {
+    if let Some(Command::VerifyArchive { from, to }) = cfg.command {
+        return archive::open_archive(&cfg.archive_dir)?.verify(from, to);
+    }
+    if let Some(Command::BackfillAttributes { batch_size }) = cfg.command {
+        let mut pg = PgConnection::establish(&cfg.postgres)?;
+        return handlers::attributes::backfill(
+            &mut pg,
+            cfg.network.chain_id(),
+            batch_size.max(1),
+        )
+        .map(|_| ());
+    }
+    if let Some(Command::BackfillCounters { batch_size }) = cfg.command {
+        let mut pg = PgConnection::establish(&cfg.postgres)?;
+        return handlers::supply::backfill(
+            &mut pg,
+            cfg.network.chain_id(),
+            batch_size.max(1),
+        )
+        .map(|_| ());
+    }
+
+    let (source, archive): (Arc<dyn CheckpointSource>, _) = match &cfg.source {
+        // replaying an archive never writes one.
+        Some(dir) => (
+            Arc::new(ArchiveSource::new(CheckpointArchive::open_read(dir)?)),
+            None,
+        ),
+        None => {
+            let sui = SuiClientBuilder::default()
+                .build(&cfg.node)
+                .await
+                .map_err(|e| anyhow!("Fullnode: {e}"))?;
+            let archive = match &cfg.archive_dir {
+                Some(dir) => {
+                    Some(Arc::new(Mutex::new(CheckpointArchive::open(dir)?)))
+                }
+                None => None,
+            };
+            (Arc::new(FullnodeSource::new(sui)), archive)
+        }
+    };
 
     let manager = ConnectionManager::<PgConnection>::new(&cfg.postgres);
     let pool = diesel::r2d2::Pool::builder()
@@ -55,20 +111,65 @@ pub async fn run(cfg: Config) -> Result<()> {
             .await
             .map_err(|e| anyhow!("RabbitMQ: {e}"))?;
 
+    let shutdown = Shutdown::listen()?;
+
     let (send, recv) = tokio::sync::mpsc::channel::<IndexingMessage>(1000);
-    tokio::spawn(async move {
-        let mut receiver = IndexSender::new(recv, conn);
-        receiver
-            .process()
-            .await
-            .expect("Unexpected error in receiver");
+    let network = cfg.network;
+    let receiver = tokio::spawn(async move {
+        IndexSender::new(recv, conn, network).process().await
     });
 
-    let mut index = Indexer::new(cfg, sui, pool, redis, send);
+    let command = cfg.command.clone();
+    let queue_capacity = cfg.queue_capacity.max(1);
+    let mut index = Indexer::new(cfg, source, pool, redis, send, archive);
+
+    if let Some(command) = command {
+        let done = match command {
+            Command::Backfill { from, to } => {
+                index.backfill(from, to, extra, shutdown).await
+            }
+            Command::RetryFailed => index.retry_failed(extra, shutdown).await,
+            Command::VerifyArchive { .. }
+            | Command::BackfillAttributes { .. }
+            | Command::BackfillCounters { .. } => {
+                unreachable!("handled above")
+            }
+        };
+        // dropping the indexer closes the channel so the sender can flush.
+        drop(index);
+        let published = join_receiver(receiver).await;
+        return done.and(published);
+    }
+
+    let (batch_sender, batch_receiver) =
+        flume::bounded::<Vec<CheckpointData>>(queue_capacity);
+
     let mut handle = index.clone();
-    tokio::spawn(async move { handle.handle_check_points().await });
+    let writer_shutdown = shutdown.clone();
+    let writer = tokio::spawn(async move {
+        handle
+            .handle_check_points(batch_receiver, extra, writer_shutdown)
+            .await
+    });
+
+    // returns on shutdown or error, closing the batch channel.
+    let downloaded = index.run_forever(batch_sender, shutdown).await;
+    drop(index);
+
+    let stored = writer.await.map_err(|e| anyhow!("Writer: {e}"))?;
+    // both indexer clones are gone, so the sender flushes and returns.
+    let published = join_receiver(receiver).await;
+
+    downloaded.and(stored).and(published)
+}
 
-    index.run_forever().await
+async fn join_receiver(
+    receiver: tokio::task::JoinHandle<lapin::Result<()>>,
+) -> Result<()> {
+    receiver
+        .await
+        .map_err(|e| anyhow!("Receiver: {e}"))?
+        .map_err(|e| anyhow!("RabbitMQ: {e}"))
 }
 
 pub async fn multi_get_full_transactions(
@@ -142,25 +243,27 @@ pub fn get_object_changes(
     Ok(created.chain(mutated).chain(unwrapped).collect())
 }
 
+/// Objects the transaction deleted, wrapped or unwrapped then deleted, with
+/// its sender and timestamp.
 pub fn get_deleted_db_objects(
     block: &SuiTransactionBlockResponse,
-) -> Result<Vec<(ObjectStatus, SuiObjectRef)>> {
+) -> Result<Vec<(ObjectStatus, SuiObjectRef, String, u64)>> {
     let effects = match block.effects.clone() {
         Some(effects) => effects,
         None => anyhow::bail!("No effects in block"),
     };
 
-    let _transaction = match block.transaction.clone() {
+    let transaction = match block.transaction.clone() {
         Some(transaction) => match transaction.data {
             V1(v1) => v1,
         },
         _ => return Err(anyhow!("Transaction is not V1")),
     };
 
-    match block.timestamp_ms {
-        Some(_) => (),
+    let timestamp = match block.timestamp_ms {
+        Some(timestamp) => timestamp,
         None => return Err(anyhow!("No timestamp in block")),
-    }
+    };
 
     let deleted = effects.deleted().iter();
     let deleted = deleted.map(|o| (ObjectStatus::Deleted, o.clone()));
@@ -173,6 +276,9 @@ pub fn get_deleted_db_objects(
     Ok(deleted
         .chain(wrapped)
         .chain(unwrapped_then_deleted)
+        .map(|(status, o)| {
+            (status, o, transaction.sender.to_string(), timestamp)
+        })
         .collect::<Vec<_>>())
 }
 
@@ -3,6 +3,7 @@ use dotenv;
 use structopt::StructOpt;
 use sui_indexer;
 
+use tracing::{error, info};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
 #[tokio::main]
@@ -25,8 +26,10 @@ async fn main() -> Result<()> {
         .expect("setting default subscriber failed");
     let config = sui_indexer::config::Config::from_args();
     if let Err(e) = sui_indexer::run(config).await {
-        panic!("Error: {}", e);
+        error!("Indexer stopped with error: {}", e);
+        std::process::exit(1);
     }
 
+    info!("Indexer stopped");
     Ok(())
 }
@@ -1,3 +1,4 @@
+use crate::models::amount::Amount;
 use crate::models::collections::Collection;
 use crate::models::tokens::Token;
 use crate::schema::activities;
@@ -18,6 +19,13 @@ pub enum ActivityType {
     Listed,
     Canceled,
     Sold,
+    OfferMade,
+    OfferCanceled,
+    Expired,
+    OfferExpired,
+    Burned,
+    Wrapped,
+    Unwrapped,
 }
 
 #[derive(Insertable, Debug, Clone, Serialize, Deserialize)]
@@ -25,7 +33,8 @@ pub enum ActivityType {
 pub struct Activity {
     pub chain_id: i64,
     pub version: i64,
-    pub tx: Option<String>,
+    /// Empty for activities no transaction produced, like expiries.
+    pub tx: String,
     pub event_account_address: String,
     pub event_creation_number: i64,
     pub event_sequence_number: i64,
@@ -38,9 +47,9 @@ pub struct Activity {
     pub transfer_type: ActivityType,
     pub from_address: Option<String>,
     pub to_address: Option<String>,
-    pub token_amount: i64,
+    pub token_amount: Amount,
     pub coin_type: Option<String>,
-    pub coin_amount: i64,
+    pub coin_amount: Amount,
     pub transaction_timestamp: chrono::NaiveDateTime,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
@@ -52,6 +61,16 @@ pub fn batch_insert(
 ) -> Result<usize> {
     insert_into(activities::table)
         .values(new)
+        .on_conflict((
+            activities::chain_id,
+            activities::collection_data_id_hash,
+            activities::token_data_id_hash,
+            activities::version,
+            activities::transfer_type,
+            activities::tx,
+            activities::event_sequence_number,
+        ))
+        .do_nothing()
         .execute(connection)
         .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
@@ -64,7 +83,7 @@ impl Activity {
         Activity {
             chain_id: collection.chain_id as i64,
             version: collection.version,
-            tx: collection.tx.clone(),
+            tx: collection.tx.clone().unwrap_or_default(),
             event_account_address: collection.creator_address.clone(),
             event_creation_number: 0,
             event_sequence_number: 0,
@@ -77,9 +96,9 @@ impl Activity {
             transfer_type: t,
             from_address: Some(collection.creator_address.clone()),
             to_address: None,
-            token_amount: 0,
+            token_amount: Amount(0),
             coin_type: None,
-            coin_amount: 0,
+            coin_amount: Amount(0),
             transaction_timestamp: chrono::Utc::now().naive_utc(),
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: chrono::Utc::now().naive_utc(),
@@ -93,7 +112,7 @@ impl Activity {
         Activity {
             chain_id: token.chain_id,
             version: token.version,
-            tx: token.tx.clone(),
+            tx: token.tx.clone().unwrap_or_default(),
             event_account_address: token.creator_address.clone(),
             event_creation_number: 0,
             event_sequence_number: 0,
@@ -106,9 +125,9 @@ impl Activity {
             transfer_type: t,
             from_address: Some(sender.clone()),
             to_address: token.owner_address.clone(),
-            token_amount: 0,
+            token_amount: Amount(0),
             coin_type: None,
-            coin_amount: 0,
+            coin_amount: Amount(0),
             transaction_timestamp: chrono::Utc::now().naive_utc(),
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: chrono::Utc::now().naive_utc(),
@@ -0,0 +1,190 @@
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::data_types::PgNumeric;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Numeric;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Base of the digits of a Postgres `numeric`.
+const NBASE: u64 = 10_000;
+
+/// A raw on-chain coin amount, a Move `u64`, stored as `numeric(20, 0)`
+/// since it does not fit an `int8`.
+#[derive(
+    AsExpression,
+    FromSqlRow,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(sql_type = Numeric)]
+pub struct Amount(pub u64);
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.0.fmt(f) }
+}
+
+impl FromStr for Amount {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { s.parse().map(Amount) }
+}
+
+impl From<Amount> for PgNumeric {
+    fn from(amount: Amount) -> Self {
+        let mut digits = vec![];
+        let mut value = amount.0;
+        while value > 0 {
+            digits.push((value % NBASE) as i16);
+            value /= NBASE;
+        }
+        digits.reverse();
+        PgNumeric::Positive {
+            weight: digits.len().saturating_sub(1) as i16,
+            scale: 0,
+            digits,
+        }
+    }
+}
+
+impl TryFrom<PgNumeric> for Amount {
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn try_from(numeric: PgNumeric) -> Result<Self, Self::Error> {
+        let (weight, digits) = match numeric {
+            PgNumeric::Positive { weight, digits, .. } => (weight, digits),
+            PgNumeric::Negative { .. } => return Err("negative amount".into()),
+            PgNumeric::NaN => return Err("amount is NaN".into()),
+        };
+        // trailing zero digits are not sent, digits past `weight` are the
+        // fraction.
+        let mut value: u64 = 0;
+        for i in 0..=weight.max(-1) {
+            let digit = digits.get(i as usize).copied().unwrap_or(0) as u64;
+            value = value
+                .checked_mul(NBASE)
+                .and_then(|v| v.checked_add(digit))
+                .ok_or("amount does not fit in a u64")?;
+        }
+        Ok(Amount(value))
+    }
+}
+
+impl FromSql<Numeric, Pg> for Amount {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        Amount::try_from(PgNumeric::from_sql(bytes)?)
+    }
+}
+
+impl ToSql<Numeric, Pg> for Amount {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let numeric = PgNumeric::from(*self);
+        <PgNumeric as ToSql<Numeric, Pg>>::to_sql(&numeric, &mut out.reborrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positive(weight: i16, digits: Vec<i16>) -> PgNumeric {
+        PgNumeric::Positive {
+            weight,
+            scale: 0,
+            digits,
+        }
+    }
+
+    #[test]
+    fn encodes_base_10000_digits() {
+        assert_eq!(PgNumeric::from(Amount(0)), positive(0, vec![]));
+        assert_eq!(PgNumeric::from(Amount(9999)), positive(0, vec![9999]));
+        assert_eq!(PgNumeric::from(Amount(10000)), positive(1, vec![1, 0]));
+        assert_eq!(
+            PgNumeric::from(Amount(u64::MAX)),
+            positive(4, vec![1844, 6744, 737, 955, 1615])
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        for value in [0, 1, 9999, 10000, 10001, 100_000_000, u64::MAX] {
+            let numeric = PgNumeric::from(Amount(value));
+            assert_eq!(Amount::try_from(numeric).unwrap(), Amount(value));
+        }
+    }
+
+    #[test]
+    fn decodes_stripped_trailing_zero_digits() {
+        // Postgres drops the trailing zero digits, `weight` still counts
+        // them.
+        assert_eq!(
+            Amount::try_from(positive(1, vec![1])).unwrap(),
+            Amount(10000)
+        );
+        assert_eq!(
+            Amount::try_from(positive(2, vec![1])).unwrap(),
+            Amount(100_000_000)
+        );
+        assert_eq!(
+            Amount::try_from(positive(4, vec![1844, 6744, 737, 955, 1615]))
+                .unwrap(),
+            Amount(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn drops_the_fraction() {
+        let numeric = PgNumeric::Positive {
+            weight: 0,
+            scale: 4,
+            digits: vec![5, 1234],
+        };
+        assert_eq!(Amount::try_from(numeric).unwrap(), Amount(5));
+        let numeric = PgNumeric::Positive {
+            weight: -1,
+            scale: 4,
+            digits: vec![1234],
+        };
+        assert_eq!(Amount::try_from(numeric).unwrap(), Amount(0));
+    }
+
+    #[test]
+    fn rejects_negative_nan_and_overflow() {
+        let negative = PgNumeric::Negative {
+            weight: 0,
+            scale: 0,
+            digits: vec![1],
+        };
+        assert!(Amount::try_from(negative).is_err());
+        assert!(Amount::try_from(PgNumeric::NaN).is_err());
+        // u64::MAX + 1
+        assert!(Amount::try_from(positive(
+            4,
+            vec![1844, 6744, 737, 955, 1616]
+        ))
+        .is_err());
+        assert!(Amount::try_from(positive(5, vec![1])).is_err());
+    }
+
+    #[test]
+    fn parses_and_displays_decimal() {
+        assert_eq!(
+            "18446744073709551615".parse::<Amount>().unwrap(),
+            Amount(u64::MAX)
+        );
+        assert!("-1".parse::<Amount>().is_err());
+        assert_eq!(Amount(10000).to_string(), "10000");
+    }
+}
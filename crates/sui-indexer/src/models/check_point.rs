@@ -9,9 +9,12 @@ use crate::schema::check_point::dsl::*;
 #[diesel(table_name = check_point)]
 pub struct CheckPoint {
     pub chain_id: i64,
+    /// The next checkpoint sequence number to index.
     pub version: i64,
 }
 
+/// Returns the next checkpoint to index for `id`, i.e. one past the last
+/// committed checkpoint.
 pub fn query_check_point(conn: &mut PgConnection, id: i64) -> Result<i64> {
     let check = check_point
         .filter(chain_id.eq(id))
@@ -0,0 +1,108 @@
+use crate::schema::coins;
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use std::collections::HashMap;
+
+pub const SUI_COIN_TYPE: &str = "0x2::sui::SUI";
+/// Row of `SUI_COIN_TYPE`, seeded by the migration.
+pub const SUI_COIN_ID: i32 = 1;
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = coins)]
+pub struct Coin {
+    pub coin_type: String,
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    pub decimals: Option<i32>,
+    pub icon_url: Option<String>,
+    /// Id of the coin's `CoinMetadata` object.
+    pub metadata_id: Option<String>,
+    /// Version of the `CoinMetadata` object.
+    pub version: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Id of `coin_type`, registering it without metadata when it was never
+/// seen. The metadata is filled once its `CoinMetadata` is indexed.
+pub fn resolve_id(
+    connection: &mut PgConnection,
+    coin_type: &str,
+    timestamp: i64,
+) -> Result<i32> {
+    if coin_type == SUI_COIN_TYPE {
+        return Ok(SUI_COIN_ID);
+    }
+    insert_into(coins::table)
+        .values((
+            coins::coin_type.eq(coin_type),
+            coins::version.eq(0),
+            coins::created_at.eq(timestamp),
+            coins::updated_at.eq(timestamp),
+        ))
+        .on_conflict(coins::coin_type)
+        .do_nothing()
+        .execute(connection)?;
+    coins::table
+        .select(coins::id)
+        .filter(coins::coin_type.eq(coin_type))
+        .first::<i32>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Upsert coin metadata, skipping any whose stored metadata version is
+/// already newer.
+pub fn batch_change(
+    connection: &mut PgConnection,
+    changed: &Vec<Coin>,
+) -> Result<usize> {
+    let types = changed
+        .iter()
+        .map(|c| c.coin_type.clone())
+        .collect::<Vec<_>>();
+    let stored: HashMap<String, i64> = coins::table
+        .select((coins::coin_type, coins::version))
+        .filter(coins::coin_type.eq_any(types))
+        .load::<(String, i64)>(connection)?
+        .into_iter()
+        .collect();
+
+    let changed = changed
+        .iter()
+        .filter(|c| stored.get(&c.coin_type).map_or(true, |v| *v <= c.version))
+        .cloned()
+        .collect::<Vec<Coin>>();
+    if changed.is_empty() {
+        return Ok(0);
+    }
+
+    insert_into(coins::table)
+        .values(&changed)
+        .on_conflict(coins::coin_type)
+        .do_update()
+        .set((
+            coins::symbol.eq(excluded(coins::symbol)),
+            coins::name.eq(excluded(coins::name)),
+            coins::decimals.eq(excluded(coins::decimals)),
+            coins::icon_url.eq(excluded(coins::icon_url)),
+            coins::metadata_id.eq(excluded(coins::metadata_id)),
+            coins::version.eq(excluded(coins::version)),
+            coins::updated_at.eq(excluded(coins::updated_at)),
+        ))
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+pub fn query_type(
+    connection: &mut PgConnection,
+    coin_id: i32,
+) -> Result<Option<String>> {
+    coins::table
+        .select(coins::coin_type)
+        .filter(coins::id.eq(coin_id))
+        .first::<String>(connection)
+        .optional()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
@@ -0,0 +1,43 @@
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+
+use crate::schema::collection_display_versions;
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = collection_display_versions)]
+pub struct CollectionDisplayVersion {
+    pub chain_id: i64,
+    pub collection_id: String,
+    pub version: i64,
+    pub display_version: i64,
+    /// The Display fields as JSON, like `collections.metadata`.
+    pub metadata: String,
+    pub tx: Option<String>,
+    pub created_at: i64,
+}
+
+pub fn batch_insert(
+    connection: &mut PgConnection,
+    versions: &Vec<CollectionDisplayVersion>,
+) -> Result<usize> {
+    insert_into(collection_display_versions::table)
+        .values(versions)
+        .on_conflict_do_nothing()
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Display history of a collection, oldest first.
+pub fn query_versions(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    collection_id: &str,
+) -> Result<Vec<CollectionDisplayVersion>> {
+    collection_display_versions::table
+        .filter(collection_display_versions::chain_id.eq(chain_id))
+        .filter(collection_display_versions::collection_id.eq(collection_id))
+        .order(collection_display_versions::version.asc())
+        .load::<CollectionDisplayVersion>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
@@ -1,9 +1,12 @@
 use anyhow::Result;
 use diesel::insert_into;
 use diesel::prelude::*;
+use diesel::sql_types::{Int8, Varchar};
+use diesel::upsert::excluded;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-use crate::schema::collections;
+use crate::schema::{collections, tokens};
 
 // "collection_name" varchar(255) NOT NULL,
 // "description" varchar NOT NULL,
@@ -43,6 +46,11 @@ pub struct Collection {
     pub last_metadata_sync: i64,
     pub created_at: i64,
     pub updated_at: i64,
+    /// Tokens ever indexed, burned ones included.
+    pub minted: i64,
+    pub burned: i64,
+    /// Addresses holding at least one token that is not burned.
+    pub holders: i64,
 }
 
 #[derive(Queryable, PartialEq, Debug, Clone)]
@@ -58,19 +66,37 @@ pub struct CollectionMetadata {
 
 pub fn query_collection(
     connection: &mut PgConnection,
+    chain: i64,
     c_id: &str,
 ) -> Result<Collection> {
     use crate::schema::collections::dsl::*;
 
     collections
+        .filter(chain_id.eq(chain as i32))
         .filter(collection_id.eq(c_id))
         .limit(1)
         .get_result::<Collection>(connection)
         .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
 
+/// Collection type to collection id pairs of one chain, used to seed the
+/// Redis lookup the token parser relies on.
+pub fn query_collection_types(
+    connection: &mut PgConnection,
+    chain: i64,
+) -> Result<Vec<(String, String)>> {
+    use crate::schema::collections::dsl::*;
+
+    collections
+        .select((collection_type, collection_id))
+        .filter(chain_id.eq(chain as i32))
+        .load::<(String, String)>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
 pub fn update_collection_metadata(
     connection: &mut PgConnection,
+    chain: i64,
     c_id: &str,
     new_meta: &Collection,
 ) -> Result<()> {
@@ -81,8 +107,8 @@ pub fn update_collection_metadata(
             (display_name.eq(new_meta.display_name.clone())),
             (description.eq(new_meta.description.clone())),
             (icon.eq(new_meta.icon.clone())),
-            (supply.eq(new_meta.supply.clone())),
         ))
+        .filter(chain_id.eq(chain as i32))
         .filter(collection_id.eq(c_id))
         .execute(connection)?;
 
@@ -95,6 +121,149 @@ pub fn batch_insert(
 ) -> Result<usize> {
     insert_into(collections::table)
         .values(new_collections)
+        .on_conflict((collections::chain_id, collections::collection_id))
+        .do_nothing()
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Upsert collections from edited Displays, skipping any whose stored
+/// version is already newer. Returns the collections that were written.
+pub fn batch_change(
+    connection: &mut PgConnection,
+    changed: &Vec<Collection>,
+) -> Result<Vec<Collection>> {
+    let chains = changed.iter().map(|c| c.chain_id).collect::<Vec<_>>();
+    let ids = changed
+        .iter()
+        .map(|c| c.collection_id.clone())
+        .collect::<Vec<_>>();
+    let stored: HashMap<(i32, String), i64> = collections::table
+        .select((
+            collections::chain_id,
+            collections::collection_id,
+            collections::version,
+        ))
+        .filter(collections::chain_id.eq_any(chains))
+        .filter(collections::collection_id.eq_any(ids))
+        .load::<(i32, String, i64)>(connection)?
+        .into_iter()
+        .map(|(chain_id, collection_id, version)| {
+            ((chain_id, collection_id), version)
+        })
+        .collect();
+
+    let changed = changed
+        .iter()
+        .filter(|c| {
+            stored
+                .get(&(c.chain_id, c.collection_id.clone()))
+                .map_or(true, |v| *v < c.version)
+        })
+        .cloned()
+        .collect::<Vec<Collection>>();
+    if changed.is_empty() {
+        return Ok(changed);
+    }
+
+    insert_into(collections::table)
+        .values(&changed)
+        .on_conflict((collections::chain_id, collections::collection_id))
+        .do_update()
+        .set((
+            collections::website.eq(excluded(collections::website)),
+            collections::description.eq(excluded(collections::description)),
+            collections::metadata_uri.eq(excluded(collections::metadata_uri)),
+            collections::metadata.eq(excluded(collections::metadata)),
+            collections::version.eq(excluded(collections::version)),
+            collections::tx.eq(excluded(collections::tx)),
+            collections::updated_at.eq(excluded(collections::updated_at)),
+        ))
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    Ok(changed)
+}
+
+/// Token counters of a collection, as stored by `refresh_counters`.
+#[derive(QueryableByName, Debug, Clone)]
+pub struct Counters {
+    #[diesel(sql_type = Varchar)]
+    pub collection_id: String,
+    #[diesel(sql_type = Int8)]
+    pub minted: i64,
+    #[diesel(sql_type = Int8)]
+    pub burned: i64,
+    #[diesel(sql_type = Int8)]
+    pub holders: i64,
+}
+
+/// Up to `limit` collection ids of the chain, in order after `after`.
+pub fn query_ids_page(
+    connection: &mut PgConnection,
+    chain: i64,
+    after: &str,
+    limit: i64,
+) -> Result<Vec<String>> {
+    use crate::schema::collections::dsl::*;
+
+    collections
+        .select(collection_id)
+        .filter(chain_id.eq(chain as i32))
+        .filter(collection_id.gt(after))
+        .order(collection_id.asc())
+        .limit(limit)
+        .load::<String>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Recount the tokens of `collection_ids` on the chain into their
+/// counters, so they always match `tokens` once the transaction commits.
+/// One aggregate over the tokens of the collections, returning the counters
+/// of those having any.
+pub fn refresh_counters(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    collection_ids: &Vec<String>,
+) -> Result<Vec<Counters>> {
+    use diesel::sql_types::{Array, Text};
+
+    diesel::sql_query(
+        "UPDATE collections c SET \
+            minted = t.minted, \
+            burned = t.burned, \
+            supply = t.minted - t.burned, \
+            holders = t.holders \
+        FROM ( \
+            SELECT collection_id, \
+                count(*) AS minted, \
+                count(*) FILTER (WHERE status = 'delete') AS burned, \
+                count(DISTINCT owner_address) \
+                    FILTER (WHERE status IS DISTINCT FROM 'delete') AS holders \
+            FROM tokens \
+            WHERE chain_id = $2 AND collection_id = ANY($1) \
+            GROUP BY collection_id \
+        ) t \
+        WHERE c.chain_id = $2 AND c.collection_id = t.collection_id \
+        RETURNING c.collection_id, c.minted, c.burned, c.holders",
+    )
+    .bind::<Array<Text>, _>(collection_ids)
+    .bind::<Int8, _>(chain_id)
+    .load::<Counters>(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+pub fn update_royalty(
+    connection: &mut PgConnection,
+    chain: i64,
+    c_type: &str,
+    new_royaltie: Option<String>,
+) -> Result<usize> {
+    use crate::schema::collections::dsl::*;
+
+    diesel::update(collections)
+        .set(royaltie.eq(new_royaltie))
+        .filter(chain_id.eq(chain as i32))
+        .filter(collection_type.eq(c_type))
         .execute(connection)
         .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::failed_items;
+use diesel_derive_enum::DbEnum;
+
+#[derive(DbEnum, Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[ExistingTypePath = "crate::schema::sql_types::FailedItemKind"]
+#[serde(rename_all = "snake_case")]
+pub enum FailedItemKind {
+    Event,
+    Object,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = failed_items)]
+pub struct FailedItem {
+    pub chain_id: i64,
+    pub checkpoint: i64,
+    pub tx_digest: String,
+    pub item_kind: FailedItemKind,
+    /// Event sequence number or object id.
+    pub item_key: String,
+    pub raw_json: String,
+    pub error: String,
+}
+
+/// Record parse failures. An item that fails again is reopened with the
+/// latest error and its attempt count bumped.
+pub fn batch_upsert(
+    connection: &mut PgConnection,
+    records: &Vec<FailedItem>,
+) -> Result<usize> {
+    insert_into(failed_items::table)
+        .values(records)
+        .on_conflict((
+            failed_items::chain_id,
+            failed_items::item_kind,
+            failed_items::tx_digest,
+            failed_items::item_key,
+        ))
+        .do_update()
+        .set((
+            failed_items::raw_json.eq(excluded(failed_items::raw_json)),
+            failed_items::error.eq(excluded(failed_items::error)),
+            failed_items::attempts.eq(failed_items::attempts + 1),
+            failed_items::resolved.eq(false),
+            failed_items::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Mark every open failure of `checkpoint` as resolved, called before the
+/// checkpoint's own failures are recorded again.
+pub fn resolve_checkpoint(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    checkpoint: i64,
+) -> Result<usize> {
+    diesel::update(
+        failed_items::table
+            .filter(failed_items::chain_id.eq(chain_id))
+            .filter(failed_items::checkpoint.eq(checkpoint))
+            .filter(failed_items::resolved.eq(false)),
+    )
+    .set((
+        failed_items::resolved.eq(true),
+        failed_items::updated_at.eq(diesel::dsl::now),
+    ))
+    .execute(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Checkpoints that still have open failures, in ascending order.
+pub fn query_unresolved_checkpoints(
+    connection: &mut PgConnection,
+    chain_id: i64,
+) -> Result<Vec<i64>> {
+    failed_items::table
+        .filter(failed_items::chain_id.eq(chain_id))
+        .filter(failed_items::resolved.eq(false))
+        .select(failed_items::checkpoint)
+        .distinct()
+        .order(failed_items::checkpoint.asc())
+        .load::<i64>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+pub fn count_unresolved(
+    connection: &mut PgConnection,
+    chain_id: i64,
+) -> Result<i64> {
+    failed_items::table
+        .filter(failed_items::chain_id.eq(chain_id))
+        .filter(failed_items::resolved.eq(false))
+        .count()
+        .get_result(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
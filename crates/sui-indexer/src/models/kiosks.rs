@@ -0,0 +1,76 @@
+use crate::schema::kiosks;
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use std::collections::HashMap;
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = kiosks)]
+pub struct Kiosk {
+    pub chain_id: i64,
+    pub kiosk_id: String,
+    pub owner_cap_id: String,
+    /// Holder of the `KioskOwnerCap`.
+    pub owner_address: String,
+    /// Version of the owner cap.
+    pub version: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Upsert kiosk owners, skipping any whose stored cap version is already
+/// newer.
+pub fn batch_change(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    changed: &Vec<Kiosk>,
+) -> Result<usize> {
+    let ids = changed
+        .iter()
+        .map(|k| k.kiosk_id.clone())
+        .collect::<Vec<_>>();
+    let stored: HashMap<String, i64> = kiosks::table
+        .select((kiosks::kiosk_id, kiosks::version))
+        .filter(kiosks::chain_id.eq(chain_id))
+        .filter(kiosks::kiosk_id.eq_any(ids))
+        .load::<(String, i64)>(connection)?
+        .into_iter()
+        .collect();
+
+    let changed = changed
+        .iter()
+        .filter(|k| stored.get(&k.kiosk_id).map_or(true, |v| *v <= k.version))
+        .cloned()
+        .collect::<Vec<Kiosk>>();
+    if changed.is_empty() {
+        return Ok(0);
+    }
+
+    insert_into(kiosks::table)
+        .values(&changed)
+        .on_conflict((kiosks::chain_id, kiosks::kiosk_id))
+        .do_update()
+        .set((
+            kiosks::owner_cap_id.eq(excluded(kiosks::owner_cap_id)),
+            kiosks::owner_address.eq(excluded(kiosks::owner_address)),
+            kiosks::version.eq(excluded(kiosks::version)),
+            kiosks::updated_at.eq(excluded(kiosks::updated_at)),
+        ))
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+pub fn query_owner(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    kiosk_id: &str,
+) -> Result<Option<String>> {
+    kiosks::table
+        .select(kiosks::owner_address)
+        .filter(kiosks::chain_id.eq(chain_id))
+        .filter(kiosks::kiosk_id.eq(kiosk_id))
+        .first::<String>(connection)
+        .optional()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
@@ -3,6 +3,7 @@ use diesel::insert_into;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::models::amount::Amount;
 use crate::schema::lists;
 use diesel_derive_enum::DbEnum;
 
@@ -34,29 +35,37 @@ pub struct List {
     pub list_time: chrono::NaiveDateTime,
     pub token_id: String,
     pub seller_address: String,
-    pub seller_value: i64,
+    pub seller_value: Amount,
     pub list_type: ListType,
     pub market_type: MarketType,
     pub expire_time: Option<chrono::NaiveDateTime>,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    pub tx_digest: String,
+    pub event_seq: i64,
 }
 
 #[derive(Queryable, Debug, Clone)]
 #[diesel(table_name = lists)]
 pub struct QueryList {
-    pub id: i64,
+    pub id: i32,
     pub chain_id: i64,
     pub coin_id: i32,
     pub list_id: String,
     pub list_time: chrono::NaiveDateTime,
     pub token_id: String,
     pub seller_address: String,
-    pub seller_value: i64,
+    pub seller_value: Amount,
+    pub expire_time: Option<chrono::NaiveDateTime>,
     pub list_type: ListType,
-    pub expire_time: chrono::NaiveDateTime,
+    pub market_type: MarketType,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    pub tx_digest: String,
+    pub event_seq: i64,
+    /// Event that closed the listing, when it was closed by one.
+    pub closed_tx_digest: Option<String>,
+    pub closed_event_seq: Option<i64>,
 }
 
 pub fn batch_insert(
@@ -65,13 +74,149 @@ pub fn batch_insert(
 ) -> Result<usize> {
     insert_into(lists::table)
         .values(records)
+        .on_conflict((lists::chain_id, lists::tx_digest, lists::event_seq))
+        .do_nothing()
         .execute(connection)
         .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
 
-pub fn delete(connection: &mut PgConnection, list_id: &str) -> Result<usize> {
-    diesel::update(lists::table.filter(lists::list_id.eq(list_id)))
-        .set(lists::list_type.eq(ListType::Canceled))
-        .execute(connection)
+/// Close the open listing `list_id` as `list_type`, leaving closed rows with
+/// the same id untouched.
+pub fn close(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    list_id: &str,
+    list_type: ListType,
+) -> Result<usize> {
+    diesel::update(
+        lists::table
+            .filter(lists::chain_id.eq(chain_id))
+            .filter(lists::list_id.eq(list_id))
+            .filter(lists::list_type.eq(ListType::Listed)),
+    )
+    .set((
+        lists::list_type.eq(list_type),
+        lists::updated_at.eq(chrono::Utc::now().naive_utc()),
+    ))
+    .execute(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Close the oldest open listing `list_id` listed at or before `time` as
+/// `list_type`, on behalf of the event `(tx_digest, event_seq)`. Replaying
+/// the event closes nothing more and returns what it closed the first time.
+pub fn close_by_event(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    list_id: &str,
+    list_type: ListType,
+    tx_digest: &str,
+    event_seq: i64,
+    time: chrono::NaiveDateTime,
+) -> Result<usize> {
+    let closed = lists::table
+        .filter(lists::chain_id.eq(chain_id))
+        .filter(lists::closed_tx_digest.eq(tx_digest))
+        .filter(lists::closed_event_seq.eq(event_seq))
+        .count()
+        .get_result::<i64>(connection)?;
+    if closed > 0 {
+        return Ok(closed as usize);
+    }
+
+    let oldest = lists::table
+        .select(lists::id)
+        .filter(lists::chain_id.eq(chain_id))
+        .filter(lists::list_id.eq(list_id))
+        .filter(lists::list_type.eq(ListType::Listed))
+        .filter(lists::list_time.le(time))
+        .order(lists::id.asc())
+        .first::<i32>(connection)
+        .optional()?;
+    match oldest {
+        Some(id) => diesel::update(lists::table.find(id))
+            .set((
+                lists::list_type.eq(list_type),
+                lists::updated_at.eq(chrono::Utc::now().naive_utc()),
+                lists::closed_tx_digest.eq(tx_digest),
+                lists::closed_event_seq.eq(event_seq),
+            ))
+            .execute(connection)
+            .map_err(|e| anyhow::anyhow!(e.to_string())),
+        None => Ok(0),
+    }
+}
+
+/// Close every open listing of `token_id` as `list_type`, returning them.
+pub fn close_by_token(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+    list_type: ListType,
+) -> Result<Vec<QueryList>> {
+    diesel::update(
+        lists::table
+            .filter(lists::chain_id.eq(chain_id))
+            .filter(lists::token_id.eq(token_id))
+            .filter(lists::list_type.eq(ListType::Listed)),
+    )
+    .set((
+        lists::list_type.eq(list_type),
+        lists::updated_at.eq(chrono::Utc::now().naive_utc()),
+    ))
+    .get_results::<QueryList>(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Seller of the latest listing with `list_id`, open or not.
+pub fn query_seller(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    list_id: &str,
+) -> Result<Option<String>> {
+    lists::table
+        .select(lists::seller_address)
+        .filter(lists::chain_id.eq(chain_id))
+        .filter(lists::list_id.eq(list_id))
+        .order(lists::id.desc())
+        .first::<String>(connection)
+        .optional()
         .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
+
+/// Token of the latest listing with `list_id`.
+pub fn query_token(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    list_id: &str,
+) -> Result<Option<String>> {
+    lists::table
+        .select(lists::token_id)
+        .filter(lists::chain_id.eq(chain_id))
+        .filter(lists::list_id.eq(list_id))
+        .order(lists::id.desc())
+        .first::<String>(connection)
+        .optional()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Close the open listings whose expiry is at or before `now` as expired,
+/// returning them.
+pub fn expire(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    now: chrono::NaiveDateTime,
+) -> Result<Vec<QueryList>> {
+    diesel::update(
+        lists::table
+            .filter(lists::chain_id.eq(chain_id))
+            .filter(lists::list_type.eq(ListType::Listed))
+            .filter(lists::expire_time.le(now)),
+    )
+    .set((
+        lists::list_type.eq(ListType::Expired),
+        lists::updated_at.eq(chrono::Utc::now().naive_utc()),
+    ))
+    .get_results::<QueryList>(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
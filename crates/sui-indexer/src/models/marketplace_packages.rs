@@ -0,0 +1,50 @@
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::marketplace_packages;
+use diesel_derive_enum::DbEnum;
+
+#[derive(
+    DbEnum, Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash,
+)]
+#[ExistingTypePath = "crate::schema::sql_types::Marketplace"]
+#[serde(rename_all = "snake_case")]
+pub enum Marketplace {
+    BobYard,
+    OriginByte,
+    OriginByteOrderbook,
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = marketplace_packages)]
+pub struct MarketplacePackage {
+    pub chain_id: i64,
+    pub package_id: String,
+    pub marketplace: Marketplace,
+    pub upgrade_cap_id: Option<String>,
+    pub version: i64,
+    pub created_at: i64,
+}
+
+pub fn batch_insert(
+    connection: &mut PgConnection,
+    packages: &Vec<MarketplacePackage>,
+) -> Result<usize> {
+    insert_into(marketplace_packages::table)
+        .values(packages)
+        .on_conflict_do_nothing()
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+pub fn query_packages(
+    connection: &mut PgConnection,
+    chain_id: i64,
+) -> Result<Vec<MarketplacePackage>> {
+    marketplace_packages::table
+        .filter(marketplace_packages::chain_id.eq(chain_id))
+        .load::<MarketplacePackage>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
@@ -1,7 +1,18 @@
 pub mod activities;
+pub mod amount;
 pub mod check_point;
+pub mod coins;
+pub mod collection_display_versions;
 pub mod collections;
+pub mod failed_items;
+pub mod kiosks;
 pub mod lists;
+pub mod marketplace_packages;
 pub mod offers;
+pub mod orderbooks;
 pub mod orders;
+pub mod token_attributes;
+pub mod token_custody;
+pub mod token_ownership_history;
 pub mod tokens;
+pub mod transfer_policies;
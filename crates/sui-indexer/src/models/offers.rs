@@ -1,3 +1,4 @@
+use crate::models::amount::Amount;
 use crate::schema::offers;
 use anyhow::Result;
 use diesel::insert_into;
@@ -24,12 +25,41 @@ pub struct Offer {
     pub offer_id: String,
     pub list_id: String,
     pub buyer_address: String,
-    pub offer_value: i64,
+    pub offer_value: Amount,
     pub offer_type: OfferType,
-    pub expire_time: chrono::NaiveDateTime,
+    pub expire_time: Option<chrono::NaiveDateTime>,
     pub offer_time: chrono::NaiveDateTime,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    pub tx_digest: String,
+    pub event_seq: i64,
+    /// Unset for collection-wide bids.
+    pub token_id: Option<String>,
+    pub collection_type: Option<String>,
+}
+
+#[derive(Queryable, Debug, Clone)]
+#[diesel(table_name = offers)]
+pub struct QueryOffer {
+    pub id: i32,
+    pub chain_id: i64,
+    pub coin_id: i32,
+    pub offer_id: String,
+    pub list_id: String,
+    pub buyer_address: String,
+    pub offer_value: Amount,
+    pub offer_type: OfferType,
+    pub expire_time: Option<chrono::NaiveDateTime>,
+    pub offer_time: chrono::NaiveDateTime,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    pub updated_at: Option<chrono::NaiveDateTime>,
+    pub tx_digest: String,
+    pub event_seq: i64,
+    pub token_id: Option<String>,
+    pub collection_type: Option<String>,
+    /// Event that closed the offer, when it was closed by one.
+    pub closed_tx_digest: Option<String>,
+    pub closed_event_seq: Option<i64>,
 }
 
 pub fn batch_insert(
@@ -38,13 +68,141 @@ pub fn batch_insert(
 ) -> Result<usize> {
     insert_into(offers::table)
         .values(records)
+        .on_conflict((offers::chain_id, offers::tx_digest, offers::event_seq))
+        .do_nothing()
         .execute(connection)
         .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
 
-pub fn delete(connection: &mut PgConnection, offer_id: &str) -> Result<usize> {
-    diesel::update(offers::table.filter(offers::offer_id.eq(offer_id)))
-        .set(offers::offer_type.eq(OfferType::Canceled))
-        .execute(connection)
+/// Close the oldest open offer `offer_id` made at or before `time` as
+/// `offer_type`, on behalf of the event `(tx_digest, event_seq)`. Replaying
+/// the event closes nothing more and returns what it closed the first
+/// time, 0 or 1.
+pub fn close_by_event(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    offer_id: &str,
+    offer_type: OfferType,
+    tx_digest: &str,
+    event_seq: i64,
+    time: chrono::NaiveDateTime,
+) -> Result<usize> {
+    let closed = offers::table
+        .filter(offers::chain_id.eq(chain_id))
+        .filter(offers::closed_tx_digest.eq(tx_digest))
+        .filter(offers::closed_event_seq.eq(event_seq))
+        .count()
+        .get_result::<i64>(connection)?;
+    if closed > 0 {
+        return Ok(closed as usize);
+    }
+
+    let oldest = offers::table
+        .filter(offers::chain_id.eq(chain_id))
+        .filter(offers::offer_id.eq(offer_id))
+        .filter(offers::offer_type.eq(OfferType::Listed))
+        .filter(offers::offer_time.le(time))
+        .select(offers::id)
+        .order(offers::id.asc())
+        .first::<i32>(connection)
+        .optional()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+    match oldest {
+        Some(id) => diesel::update(offers::table.find(id))
+            .set((
+                offers::offer_type.eq(offer_type),
+                offers::updated_at.eq(chrono::Utc::now().naive_utc()),
+                offers::closed_tx_digest.eq(tx_digest),
+                offers::closed_event_seq.eq(event_seq),
+            ))
+            .execute(connection)
+            .map_err(|e| anyhow::anyhow!(e.to_string())),
+        None => Ok(0),
+    }
+}
+
+/// Latest offer with `offer_id`, open or not.
+pub fn query_offer(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    offer_id: &str,
+) -> Result<Option<QueryOffer>> {
+    offers::table
+        .filter(offers::chain_id.eq(chain_id))
+        .filter(offers::offer_id.eq(offer_id))
+        .order(offers::id.desc())
+        .first::<QueryOffer>(connection)
+        .optional()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Highest open offer that can fill `token_id`: offers on the token itself
+/// and bids on its whole collection.
+pub fn best_token_offer(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+    collection_type: &str,
+) -> Result<Option<QueryOffer>> {
+    offers::table
+        .filter(offers::chain_id.eq(chain_id))
+        .filter(offers::offer_type.eq(OfferType::Listed))
+        .filter(
+            offers::expire_time
+                .is_null()
+                .or(offers::expire_time.gt(chrono::Utc::now().naive_utc())),
+        )
+        .filter(
+            offers::token_id.eq(token_id).or(offers::token_id
+                .is_null()
+                .and(offers::collection_type.eq(collection_type))),
+        )
+        .order(offers::offer_value.desc())
+        .first::<QueryOffer>(connection)
+        .optional()
         .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
+
+/// Highest open collection-wide bid on `collection_type`.
+pub fn best_collection_offer(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    collection_type: &str,
+) -> Result<Option<QueryOffer>> {
+    offers::table
+        .filter(offers::chain_id.eq(chain_id))
+        .filter(offers::offer_type.eq(OfferType::Listed))
+        .filter(
+            offers::expire_time
+                .is_null()
+                .or(offers::expire_time.gt(chrono::Utc::now().naive_utc())),
+        )
+        .filter(offers::token_id.is_null())
+        .filter(offers::collection_type.eq(collection_type))
+        .order(offers::offer_value.desc())
+        .first::<QueryOffer>(connection)
+        .optional()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Close the open offers whose expiry is at or before `now` as expired,
+/// returning them.
+pub fn expire(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    now: chrono::NaiveDateTime,
+) -> Result<Vec<QueryOffer>> {
+    diesel::update(
+        offers::table
+            .filter(offers::chain_id.eq(chain_id))
+            .filter(offers::offer_type.eq(OfferType::Listed))
+            .filter(offers::expire_time.le(now)),
+    )
+    .set((
+        offers::offer_type.eq(OfferType::Expired),
+        offers::updated_at.eq(chrono::Utc::now().naive_utc()),
+    ))
+    .get_results::<QueryOffer>(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
@@ -0,0 +1,45 @@
+use crate::models::coins::SUI_COIN_ID;
+use crate::schema::orderbooks;
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = orderbooks)]
+pub struct Orderbook {
+    pub chain_id: i64,
+    pub orderbook_id: String,
+    pub nft_type: String,
+    /// Coin the book trades in.
+    pub coin_id: i32,
+    pub created_at: i64,
+}
+
+/// Books are created once, a replayed creation changes nothing.
+pub fn insert(
+    connection: &mut PgConnection,
+    orderbook: &Orderbook,
+) -> Result<usize> {
+    insert_into(orderbooks::table)
+        .values(orderbook)
+        .on_conflict((orderbooks::chain_id, orderbooks::orderbook_id))
+        .do_nothing()
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Coin of the orderbook, SUI for books created before indexing started.
+pub fn query_coin_id(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    orderbook_id: &str,
+) -> Result<i32> {
+    orderbooks::table
+        .select(orderbooks::coin_id)
+        .filter(orderbooks::chain_id.eq(chain_id))
+        .filter(orderbooks::orderbook_id.eq(orderbook_id))
+        .first::<i32>(connection)
+        .optional()
+        .map(|coin_id| coin_id.unwrap_or(SUI_COIN_ID))
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
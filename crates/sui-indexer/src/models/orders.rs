@@ -1,3 +1,4 @@
+use crate::models::amount::Amount;
 use crate::schema::orders;
 use anyhow::Result;
 use diesel::insert_into;
@@ -25,10 +26,12 @@ pub struct Order {
     pub seller_address: String,
     pub buyer_address: String,
     pub order_type: OrderType,
-    pub value: i64,
+    pub value: Amount,
     pub sell_time: chrono::NaiveDateTime,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    pub tx_digest: String,
+    pub event_seq: i64,
 }
 
 pub fn batch_insert(
@@ -37,6 +40,8 @@ pub fn batch_insert(
 ) -> Result<usize> {
     insert_into(orders::table)
         .values(records)
+        .on_conflict((orders::chain_id, orders::tx_digest, orders::event_seq))
+        .do_nothing()
         .execute(connection)
         .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
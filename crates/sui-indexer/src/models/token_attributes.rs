@@ -0,0 +1,111 @@
+use crate::models::tokens::TokenStatus;
+use crate::schema::{token_attributes, tokens};
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = token_attributes)]
+pub struct TokenAttribute {
+    pub chain_id: i64,
+    pub token_id: String,
+    pub collection_id: String,
+    pub trait_type: String,
+    pub value: String,
+    /// Version of the token the attributes were parsed from.
+    pub version: i64,
+}
+
+/// Replace the attributes of `token_id` with `attributes` parsed at
+/// `version`, unless the token is burned or stored at a newer version, or
+/// the stored attributes come from a newer version.
+pub fn replace(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+    version: i64,
+    attributes: &Vec<TokenAttribute>,
+) -> Result<usize> {
+    let token = tokens::table
+        .select((tokens::status, tokens::version))
+        .filter(tokens::chain_id.eq(chain_id))
+        .filter(tokens::token_id.eq(token_id))
+        .first::<(Option<TokenStatus>, i64)>(connection)
+        .optional()?;
+    if let Some((status, stored)) = token {
+        if status == Some(TokenStatus::DELETE) || stored > version {
+            return Ok(0);
+        }
+    }
+
+    let stored = token_attributes::table
+        .select(diesel::dsl::max(token_attributes::version))
+        .filter(token_attributes::chain_id.eq(chain_id))
+        .filter(token_attributes::token_id.eq(token_id))
+        .first::<Option<i64>>(connection)?;
+    if stored.map_or(false, |v| v > version) {
+        return Ok(0);
+    }
+
+    delete(connection, chain_id, token_id)?;
+    insert_into(token_attributes::table)
+        .values(attributes)
+        .on_conflict_do_nothing()
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+pub fn delete(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+) -> Result<usize> {
+    diesel::delete(
+        token_attributes::table
+            .filter(token_attributes::chain_id.eq(chain_id))
+            .filter(token_attributes::token_id.eq(token_id)),
+    )
+    .execute(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// `(trait_type, value, tokens)` of every trait in the collection, burned
+/// tokens excluded.
+pub fn trait_counts(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    collection_id: &str,
+) -> Result<Vec<(String, String, i64)>> {
+    use diesel::dsl::count_star;
+
+    token_attributes::table
+        .filter(token_attributes::chain_id.eq(chain_id))
+        .filter(token_attributes::collection_id.eq(collection_id))
+        .group_by((token_attributes::trait_type, token_attributes::value))
+        .select((
+            token_attributes::trait_type,
+            token_attributes::value,
+            count_star(),
+        ))
+        .order((token_attributes::trait_type, count_star().desc()))
+        .load::<(String, String, i64)>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Tokens of the collection having `value` for `trait_type`.
+pub fn query_tokens_with_trait(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    collection_id: &str,
+    trait_type: &str,
+    value: &str,
+) -> Result<Vec<String>> {
+    token_attributes::table
+        .select(token_attributes::token_id)
+        .filter(token_attributes::chain_id.eq(chain_id))
+        .filter(token_attributes::collection_id.eq(collection_id))
+        .filter(token_attributes::trait_type.eq(trait_type))
+        .filter(token_attributes::value.eq(value))
+        .load::<String>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
@@ -0,0 +1,123 @@
+use crate::schema::token_custody;
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = token_custody)]
+pub struct TokenCustody {
+    pub chain_id: i64,
+    pub token_id: String,
+    /// Object the token was wrapped into, when it changed in the same
+    /// transaction.
+    pub custodian_id: Option<String>,
+    pub custodian_type: Option<String>,
+    /// `package::module` of the Move call that wrapped the token.
+    pub protocol: Option<String>,
+    /// Owner of the token when it was wrapped.
+    pub owner_address: Option<String>,
+    /// Version of the token at its last wrap or unwrap.
+    pub version: i64,
+    pub tx_digest: String,
+    pub wrapped_at: i64,
+    pub unwrapped_at: Option<i64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Record the token as held by `custody`, unless its stored custody is
+/// already as new, so a replayed wrap reports nothing recorded.
+pub fn wrap(
+    connection: &mut PgConnection,
+    custody: &TokenCustody,
+) -> Result<usize> {
+    let stored = token_custody::table
+        .select(token_custody::version)
+        .filter(token_custody::chain_id.eq(custody.chain_id))
+        .filter(token_custody::token_id.eq(&custody.token_id))
+        .first::<i64>(connection)
+        .optional()?;
+    if stored.map_or(false, |v| v >= custody.version) {
+        return Ok(0);
+    }
+
+    insert_into(token_custody::table)
+        .values(custody)
+        .on_conflict((token_custody::chain_id, token_custody::token_id))
+        .do_update()
+        .set((
+            token_custody::custodian_id
+                .eq(excluded(token_custody::custodian_id)),
+            token_custody::custodian_type
+                .eq(excluded(token_custody::custodian_type)),
+            token_custody::protocol.eq(excluded(token_custody::protocol)),
+            token_custody::owner_address
+                .eq(excluded(token_custody::owner_address)),
+            token_custody::version.eq(excluded(token_custody::version)),
+            token_custody::tx_digest.eq(excluded(token_custody::tx_digest)),
+            token_custody::wrapped_at.eq(excluded(token_custody::wrapped_at)),
+            token_custody::unwrapped_at.eq(None::<i64>),
+            token_custody::updated_at.eq(excluded(token_custody::updated_at)),
+        ))
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Close the open custody of the token unwrapped at `version`, returning
+/// it.
+pub fn release(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+    version: i64,
+    tx_digest: &str,
+    timestamp: i64,
+) -> Result<Option<TokenCustody>> {
+    diesel::update(
+        token_custody::table
+            .filter(token_custody::chain_id.eq(chain_id))
+            .filter(token_custody::token_id.eq(token_id))
+            .filter(token_custody::unwrapped_at.is_null())
+            .filter(token_custody::version.le(version)),
+    )
+    .set((
+        token_custody::version.eq(version),
+        token_custody::tx_digest.eq(tx_digest),
+        token_custody::unwrapped_at.eq(timestamp),
+        token_custody::updated_at.eq(timestamp),
+    ))
+    .get_result::<TokenCustody>(connection)
+    .optional()
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Current custody of the token, unset when it is not wrapped.
+pub fn query_custody(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+) -> Result<Option<TokenCustody>> {
+    token_custody::table
+        .filter(token_custody::chain_id.eq(chain_id))
+        .filter(token_custody::token_id.eq(token_id))
+        .filter(token_custody::unwrapped_at.is_null())
+        .first::<TokenCustody>(connection)
+        .optional()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Tokens of `owner_address` that are currently wrapped.
+pub fn query_by_owner(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    owner_address: &str,
+) -> Result<Vec<TokenCustody>> {
+    token_custody::table
+        .filter(token_custody::chain_id.eq(chain_id))
+        .filter(token_custody::owner_address.eq(owner_address))
+        .filter(token_custody::unwrapped_at.is_null())
+        .order(token_custody::wrapped_at.desc())
+        .load::<TokenCustody>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
@@ -0,0 +1,226 @@
+use crate::models::tokens::OwnerKind;
+use crate::schema::{token_ownership_history, tokens};
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = token_ownership_history)]
+pub struct Ownership {
+    pub chain_id: i64,
+    pub token_id: String,
+    pub owner_address: Option<String>,
+    pub owner_kind: Option<OwnerKind>,
+    pub from_version: i64,
+    pub from_checkpoint: i64,
+    /// Milliseconds since the epoch.
+    pub from_time: i64,
+    pub tx_digest: Option<String>,
+}
+
+#[derive(Queryable, Debug, Clone)]
+#[diesel(table_name = token_ownership_history)]
+pub struct QueryOwnership {
+    pub id: i64,
+    pub chain_id: i64,
+    pub token_id: String,
+    pub owner_address: Option<String>,
+    pub owner_kind: Option<OwnerKind>,
+    pub from_version: i64,
+    pub from_checkpoint: i64,
+    pub from_time: i64,
+    /// Unset while this owner still holds the token.
+    pub to_version: Option<i64>,
+    pub to_checkpoint: Option<i64>,
+    pub to_time: Option<i64>,
+    pub tx_digest: Option<String>,
+}
+
+impl From<QueryOwnership> for Ownership {
+    fn from(o: QueryOwnership) -> Self {
+        Ownership {
+            chain_id: o.chain_id,
+            token_id: o.token_id,
+            owner_address: o.owner_address,
+            owner_kind: o.owner_kind,
+            from_version: o.from_version,
+            from_checkpoint: o.from_checkpoint,
+            from_time: o.from_time,
+            tx_digest: o.tx_digest,
+        }
+    }
+}
+
+/// Whether a closed ownership of the token spans `version`, i.e. it was
+/// already recorded and ended since.
+fn covered(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+    version: i64,
+) -> Result<bool> {
+    diesel::select(diesel::dsl::exists(
+        token_ownership_history::table
+            .filter(token_ownership_history::chain_id.eq(chain_id))
+            .filter(token_ownership_history::token_id.eq(token_id))
+            .filter(token_ownership_history::from_version.le(version))
+            .filter(token_ownership_history::to_version.gt(version)),
+    ))
+    .get_result::<bool>(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// End the open ownership of the token at `version`.
+pub fn close(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+    version: i64,
+    checkpoint: i64,
+    time: i64,
+) -> Result<usize> {
+    diesel::update(
+        token_ownership_history::table
+            .filter(token_ownership_history::chain_id.eq(chain_id))
+            .filter(token_ownership_history::token_id.eq(token_id))
+            .filter(token_ownership_history::to_version.is_null())
+            .filter(token_ownership_history::from_version.lt(version)),
+    )
+    .set((
+        token_ownership_history::to_version.eq(version),
+        token_ownership_history::to_checkpoint.eq(checkpoint),
+        token_ownership_history::to_time.eq(time),
+    ))
+    .execute(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Start a new ownership for every token whose owner differs from its open
+/// one, closing the latter. `changed` must be in version order; versions
+/// at or below the open one are skipped so replays change nothing. Without
+/// an open ownership, versions a closed one spans are skipped, and so are
+/// tokens stored at a newer version than any of theirs in `changed`.
+pub fn batch_record(
+    connection: &mut PgConnection,
+    changed: &Vec<Ownership>,
+) -> Result<usize> {
+    let chains = changed.iter().map(|o| o.chain_id).collect::<Vec<_>>();
+    let ids = changed
+        .iter()
+        .map(|o| o.token_id.clone())
+        .collect::<Vec<_>>();
+    let mut open: HashMap<(i64, String), Ownership> =
+        token_ownership_history::table
+            .filter(token_ownership_history::chain_id.eq_any(&chains))
+            .filter(token_ownership_history::token_id.eq_any(&ids))
+            .filter(token_ownership_history::to_version.is_null())
+            .load::<QueryOwnership>(connection)?
+            .into_iter()
+            .map(|o| ((o.chain_id, o.token_id.clone()), o.into()))
+            .collect();
+    let stored: HashMap<(i64, String), i64> = tokens::table
+        .select((tokens::chain_id, tokens::token_id, tokens::version))
+        .filter(tokens::chain_id.eq_any(&chains))
+        .filter(tokens::token_id.eq_any(&ids))
+        .load::<(i64, String, i64)>(connection)?
+        .into_iter()
+        .map(|(chain_id, token_id, version)| ((chain_id, token_id), version))
+        .collect();
+    let mut latest: HashMap<(i64, String), i64> = HashMap::new();
+    for new in changed.iter() {
+        let version = latest
+            .entry((new.chain_id, new.token_id.clone()))
+            .or_insert(new.from_version);
+        *version = (*version).max(new.from_version);
+    }
+
+    let mut recorded = 0;
+    for new in changed.iter() {
+        let key = (new.chain_id, new.token_id.clone());
+        match open.get(&key) {
+            Some(current) => {
+                if current.from_version >= new.from_version
+                    || (current.owner_address == new.owner_address
+                        && current.owner_kind == new.owner_kind)
+                {
+                    continue;
+                }
+            }
+            None => {
+                if stored.get(&key).map_or(false, |v| *v > latest[&key])
+                    || covered(
+                        connection,
+                        new.chain_id,
+                        &new.token_id,
+                        new.from_version,
+                    )?
+                {
+                    continue;
+                }
+            }
+        }
+        close(
+            connection,
+            new.chain_id,
+            &new.token_id,
+            new.from_version,
+            new.from_checkpoint,
+            new.from_time,
+        )?;
+        recorded += insert_into(token_ownership_history::table)
+            .values(new)
+            .on_conflict((
+                token_ownership_history::chain_id,
+                token_ownership_history::token_id,
+                token_ownership_history::from_version,
+            ))
+            .do_nothing()
+            .execute(connection)?;
+        open.insert(key, new.clone());
+    }
+    Ok(recorded)
+}
+
+/// Who held the token at the end of `checkpoint`.
+pub fn owner_at_checkpoint(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+    checkpoint: i64,
+) -> Result<Option<QueryOwnership>> {
+    token_ownership_history::table
+        .filter(token_ownership_history::chain_id.eq(chain_id))
+        .filter(token_ownership_history::token_id.eq(token_id))
+        .filter(token_ownership_history::from_checkpoint.le(checkpoint))
+        .filter(
+            token_ownership_history::to_checkpoint
+                .is_null()
+                .or(token_ownership_history::to_checkpoint.gt(checkpoint)),
+        )
+        .order(token_ownership_history::from_version.desc())
+        .first::<QueryOwnership>(connection)
+        .optional()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Tokens `owner_address` held at `time`, in milliseconds since the epoch.
+pub fn held_at_time(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    owner_address: &str,
+    time: i64,
+) -> Result<Vec<QueryOwnership>> {
+    token_ownership_history::table
+        .filter(token_ownership_history::chain_id.eq(chain_id))
+        .filter(token_ownership_history::owner_address.eq(owner_address))
+        .filter(token_ownership_history::from_time.le(time))
+        .filter(
+            token_ownership_history::to_time
+                .is_null()
+                .or(token_ownership_history::to_time.gt(time)),
+        )
+        .order(token_ownership_history::from_time.asc())
+        .load::<QueryOwnership>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
@@ -5,6 +5,7 @@ use diesel::prelude::*;
 use diesel::upsert::excluded;
 use diesel_derive_enum::DbEnum;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(DbEnum, Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
 #[ExistingTypePath = "crate::schema::sql_types::TokenStatus"]
@@ -14,6 +15,18 @@ pub enum TokenStatus {
     DELETE,
 }
 
+/// What holds a token, see `handlers::owner`.
+#[derive(DbEnum, Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[ExistingTypePath = "crate::schema::sql_types::OwnerKind"]
+#[serde(rename_all = "snake_case")]
+pub enum OwnerKind {
+    Address,
+    Kiosk,
+    Object,
+    Shared,
+    Immutable,
+}
+
 #[derive(
     Insertable, Queryable, PartialEq, Debug, Clone, Serialize, Deserialize,
 )]
@@ -39,11 +52,68 @@ pub struct Token {
     pub status: TokenStatus,
     pub created_at: Option<i64>,
     pub updated_at: Option<i64>,
+    pub owner_kind: Option<OwnerKind>,
+}
+
+/// A `tokens` row as stored, `status` may be unset on old rows.
+#[derive(Queryable, Debug, Clone)]
+#[diesel(table_name = tokens)]
+pub struct QueryToken {
+    pub chain_id: i64,
+    pub token_id: String,
+    pub collection_id: String,
+    pub creator_address: String,
+    pub collection_type: String,
+    pub collection_name: String,
+    pub token_name: String,
+    pub attributes: Option<String>,
+    pub version: i64,
+    pub payee_address: String,
+    pub royalty_points_numerator: i64,
+    pub royalty_points_denominator: i64,
+    pub owner_address: Option<String>,
+    pub metadata_uri: String,
+    pub metadata_json: Option<String>,
+    pub image: Option<String>,
+    pub tx: Option<String>,
+    pub status: Option<TokenStatus>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub owner_kind: Option<OwnerKind>,
+}
+
+impl From<QueryToken> for Token {
+    fn from(token: QueryToken) -> Self {
+        Token {
+            chain_id: token.chain_id,
+            token_id: token.token_id,
+            collection_id: token.collection_id,
+            creator_address: token.creator_address,
+            collection_type: token.collection_type,
+            collection_name: token.collection_name,
+            token_name: token.token_name,
+            attributes: token.attributes,
+            version: token.version,
+            payee_address: token.payee_address,
+            royalty_points_numerator: token.royalty_points_numerator,
+            royalty_points_denominator: token.royalty_points_denominator,
+            owner_address: token.owner_address,
+            metadata_uri: token.metadata_uri,
+            metadata_json: token.metadata_json,
+            image: token.image,
+            tx: token.tx,
+            status: token.status.unwrap_or(TokenStatus::EXIST),
+            created_at: Some(token.created_at),
+            updated_at: Some(token.updated_at),
+            owner_kind: token.owner_kind,
+        }
+    }
 }
 
 #[derive(Queryable, PartialEq, Debug, Clone)]
 #[diesel(table_name = tokens)]
 pub struct Metadata {
+    pub chain_id: i64,
     pub token_id: String,
     pub metadata_json: Option<String>,
     pub metadata_uri: String,
@@ -56,24 +126,54 @@ pub fn batch_insert(
 ) -> Result<usize> {
     insert_into(tokens::table)
         .values(new_tokens)
-        .on_conflict(tokens::token_id)
+        .on_conflict((tokens::chain_id, tokens::token_id))
         .do_nothing()
         .execute(connection)
         .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
 
+/// Upsert the changed tokens, skipping any whose stored version is already
+/// newer so replaying an older checkpoint never rolls a token back.
 pub fn batch_change(
     connection: &mut PgConnection,
     changed: &Vec<Token>,
 ) -> Result<usize> {
+    let chains = changed.iter().map(|t| t.chain_id).collect::<Vec<_>>();
+    let ids = changed
+        .iter()
+        .map(|t| t.token_id.clone())
+        .collect::<Vec<_>>();
+    let stored: HashMap<(i64, String), i64> = tokens::table
+        .select((tokens::chain_id, tokens::token_id, tokens::version))
+        .filter(tokens::chain_id.eq_any(chains))
+        .filter(tokens::token_id.eq_any(ids))
+        .load::<(i64, String, i64)>(connection)?
+        .into_iter()
+        .map(|(chain_id, token_id, version)| ((chain_id, token_id), version))
+        .collect();
+
+    let changed = changed
+        .iter()
+        .filter(|t| {
+            stored
+                .get(&(t.chain_id, t.token_id.clone()))
+                .map_or(true, |v| *v <= t.version)
+        })
+        .cloned()
+        .collect::<Vec<Token>>();
+    if changed.is_empty() {
+        return Ok(0);
+    }
+
     insert_into(tokens::table)
-        .values(changed)
-        .on_conflict(tokens::token_id)
+        .values(&changed)
+        .on_conflict((tokens::chain_id, tokens::token_id))
         .do_update()
         .set((
             tokens::metadata_json.eq(excluded(tokens::metadata_json)),
             tokens::version.eq(excluded(tokens::version)),
             tokens::owner_address.eq(excluded(tokens::owner_address)),
+            tokens::owner_kind.eq(excluded(tokens::owner_kind)),
             tokens::updated_at.eq(excluded(tokens::updated_at)),
             tokens::tx.eq(excluded(tokens::tx)),
             //tokens::image.eq(excluded(tokens::image)),
@@ -82,13 +182,50 @@ pub fn batch_change(
         .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
 
+pub fn query_token(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+) -> Result<Option<Token>> {
+    tokens::table
+        .filter(tokens::chain_id.eq(chain_id))
+        .filter(tokens::token_id.eq(token_id))
+        .first::<QueryToken>(connection)
+        .optional()
+        .map(|token| token.map(Token::from))
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Up to `limit` tokens of the chain that are not burned, in `token_id`
+/// order after `after`.
+pub fn query_page(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    after: &str,
+    limit: i64,
+) -> Result<Vec<Token>> {
+    tokens::table
+        .filter(tokens::chain_id.eq(chain_id))
+        .filter(tokens::token_id.gt(after))
+        .filter(
+            tokens::status
+                .is_null()
+                .or(tokens::status.ne(TokenStatus::DELETE)),
+        )
+        .order(tokens::token_id.asc())
+        .limit(limit)
+        .load::<QueryToken>(connection)
+        .map(|tokens| tokens.into_iter().map(Token::from).collect())
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
 pub fn query_the_uncache_images(
     connection: &mut PgConnection,
 ) -> Result<Vec<Metadata>> {
     use crate::schema::tokens::dsl::*;
 
     tokens
-        .select((token_id, metadata_json, metadata_uri, image))
+        .select((chain_id, token_id, metadata_json, metadata_uri, image))
         .filter(image.is_null())
         .limit(1000)
         .get_results::<Metadata>(connection)
@@ -97,47 +234,90 @@ pub fn query_the_uncache_images(
 
 pub fn update_image_url(
     connection: &mut PgConnection,
+    chain: i64,
     token_id_for_update: String,
     images_url: Option<String>,
 ) -> Result<()> {
     use crate::schema::tokens::dsl::*;
-    let _ = diesel::update(tokens.filter(token_id.eq(token_id_for_update)))
-        .set(image.eq(images_url))
-        .execute(connection)?;
+    let _ = diesel::update(
+        tokens
+            .filter(chain_id.eq(chain))
+            .filter(token_id.eq(token_id_for_update)),
+    )
+    .set(image.eq(images_url))
+    .execute(connection)?;
 
     Ok(())
 }
 
-/* pub fn count_star(conn: &mut PgConnection, c_id: String) -> Result<i64> {
-    use crate::schema::tokens::dsl::*;
-    use diesel::dsl::count_star;
-
-    let a = tokens
-        .select(count_star())
-        .filter(collection_id.eq(c_id))
-        .filter(status.eq(TokenStatus::EXIST))
-        .first::<i64>(conn)
-        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-
-    Ok(a)
-} */
-
-pub fn set_status_delete(
+/// Mark the token burned at `version`, returning it unless it was unknown,
+/// already burned or stored at the same or a newer version, so replaying an
+/// older delete never burns a newer row.
+pub fn burn(
     connection: &mut PgConnection,
-    t_id: &str,
-) -> Result<()> {
-    use crate::schema::tokens::dsl::*;
-
-    tracing::info!(id = t_id, "Set status_delete",);
+    chain_id: i64,
+    token_id: &str,
+    version: i64,
+    tx: &str,
+    timestamp: i64,
+) -> Result<Option<Token>> {
+    diesel::update(
+        tokens::table
+            .filter(tokens::chain_id.eq(chain_id))
+            .filter(tokens::token_id.eq(token_id))
+            .filter(tokens::version.lt(version))
+            .filter(
+                tokens::status
+                    .is_null()
+                    .or(tokens::status.ne(TokenStatus::DELETE)),
+            ),
+    )
+    .set((
+        tokens::status.eq(TokenStatus::DELETE),
+        tokens::version.eq(version),
+        tokens::tx.eq(tx),
+        tokens::updated_at.eq(timestamp),
+    ))
+    .get_result::<QueryToken>(connection)
+    .optional()
+    .map(|token| token.map(Token::from))
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
 
-    let _ = diesel::update(tokens.filter(token_id.eq(t_id)))
-        .set(status.eq(TokenStatus::DELETE))
-        .execute(connection)?;
-    Ok(())
+pub fn delete(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    token_id: &str,
+) -> Result<usize> {
+    diesel::delete(
+        tokens::table
+            .filter(tokens::chain_id.eq(chain_id))
+            .filter(tokens::token_id.eq(token_id)),
+    )
+    .execute(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
 
-pub fn delete(connection: &mut PgConnection, token_id: &str) -> Result<usize> {
-    diesel::delete(tokens::table.filter(tokens::token_id.eq(token_id)))
-        .execute(connection)
-        .map_err(|e| anyhow::anyhow!(e.to_string()))
+/// Set the royalty of every token of `collection_type`, used when its
+/// transfer policy changes.
+pub fn update_royalty(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    collection_type: &str,
+    numerator: i64,
+    denominator: i64,
+    payee_address: &str,
+) -> Result<usize> {
+    diesel::update(
+        tokens::table
+            .filter(tokens::chain_id.eq(chain_id))
+            .filter(tokens::collection_type.eq(collection_type)),
+    )
+    .set((
+        tokens::royalty_points_numerator.eq(numerator),
+        tokens::royalty_points_denominator.eq(denominator),
+        tokens::payee_address.eq(payee_address),
+    ))
+    .execute(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
 }
@@ -0,0 +1,235 @@
+use crate::schema::{transfer_policies, transfer_policy_rules};
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use std::collections::HashMap;
+
+/// Basis points denominator of `royalty_bp`.
+pub const ROYALTY_DENOMINATOR: i64 = 10_000;
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = transfer_policies)]
+pub struct TransferPolicy {
+    pub chain_id: i64,
+    pub nft_type: String,
+    pub policy_id: String,
+    /// JSON array of the attached rule types.
+    pub rules: String,
+    pub royalty_bp: Option<i64>,
+    pub royalty_min_amount: Option<i64>,
+    pub floor_price: Option<i64>,
+    pub kiosk_lock: bool,
+    pub payee_address: Option<String>,
+    pub version: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = transfer_policy_rules)]
+pub struct TransferPolicyRule {
+    pub chain_id: i64,
+    pub policy_id: String,
+    pub rule_type: String,
+    /// JSON of the rule's config.
+    pub config: String,
+    pub version: i64,
+    pub updated_at: i64,
+}
+
+/// Upsert changed policies and drop the configs of rules they no longer
+/// have. The rule summary columns are left to `update_summary`.
+pub fn batch_change(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    changed: &Vec<TransferPolicy>,
+) -> Result<usize> {
+    let ids = changed
+        .iter()
+        .map(|p| p.policy_id.clone())
+        .collect::<Vec<_>>();
+    let stored: HashMap<String, i64> = transfer_policies::table
+        .select((transfer_policies::policy_id, transfer_policies::version))
+        .filter(transfer_policies::chain_id.eq(chain_id))
+        .filter(transfer_policies::policy_id.eq_any(ids))
+        .load::<(String, i64)>(connection)?
+        .into_iter()
+        .collect();
+
+    let changed = changed
+        .iter()
+        .filter(|p| stored.get(&p.policy_id).map_or(true, |v| *v <= p.version))
+        .cloned()
+        .collect::<Vec<TransferPolicy>>();
+    if changed.is_empty() {
+        return Ok(0);
+    }
+
+    for policy in changed.iter() {
+        let rules: Vec<String> = serde_json::from_str(&policy.rules)?;
+        diesel::delete(
+            transfer_policy_rules::table
+                .filter(transfer_policy_rules::chain_id.eq(chain_id))
+                .filter(transfer_policy_rules::policy_id.eq(&policy.policy_id))
+                .filter(transfer_policy_rules::rule_type.ne_all(rules)),
+        )
+        .execute(connection)?;
+    }
+
+    insert_into(transfer_policies::table)
+        .values(&changed)
+        .on_conflict((transfer_policies::chain_id, transfer_policies::nft_type))
+        .do_update()
+        .set((
+            transfer_policies::policy_id
+                .eq(excluded(transfer_policies::policy_id)),
+            transfer_policies::rules.eq(excluded(transfer_policies::rules)),
+            transfer_policies::version.eq(excluded(transfer_policies::version)),
+            transfer_policies::updated_at
+                .eq(excluded(transfer_policies::updated_at)),
+        ))
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Upsert rule configs, skipping any whose stored version is newer.
+pub fn batch_change_rules(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    changed: &Vec<TransferPolicyRule>,
+) -> Result<usize> {
+    let ids = changed
+        .iter()
+        .map(|r| r.policy_id.clone())
+        .collect::<Vec<_>>();
+    let stored: HashMap<(String, String), i64> = transfer_policy_rules::table
+        .select((
+            transfer_policy_rules::policy_id,
+            transfer_policy_rules::rule_type,
+            transfer_policy_rules::version,
+        ))
+        .filter(transfer_policy_rules::chain_id.eq(chain_id))
+        .filter(transfer_policy_rules::policy_id.eq_any(ids))
+        .load::<(String, String, i64)>(connection)?
+        .into_iter()
+        .map(|(policy_id, rule_type, version)| {
+            ((policy_id, rule_type), version)
+        })
+        .collect();
+
+    let changed = changed
+        .iter()
+        .filter(|r| {
+            stored
+                .get(&(r.policy_id.clone(), r.rule_type.clone()))
+                .map_or(true, |v| *v <= r.version)
+        })
+        .cloned()
+        .collect::<Vec<TransferPolicyRule>>();
+    if changed.is_empty() {
+        return Ok(0);
+    }
+
+    insert_into(transfer_policy_rules::table)
+        .values(&changed)
+        .on_conflict((
+            transfer_policy_rules::chain_id,
+            transfer_policy_rules::policy_id,
+            transfer_policy_rules::rule_type,
+        ))
+        .do_update()
+        .set((
+            transfer_policy_rules::config
+                .eq(excluded(transfer_policy_rules::config)),
+            transfer_policy_rules::version
+                .eq(excluded(transfer_policy_rules::version)),
+            transfer_policy_rules::updated_at
+                .eq(excluded(transfer_policy_rules::updated_at)),
+        ))
+        .execute(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+pub fn set_payee(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    policy_id: &str,
+    payee_address: &str,
+) -> Result<usize> {
+    diesel::update(
+        transfer_policies::table
+            .filter(transfer_policies::chain_id.eq(chain_id))
+            .filter(transfer_policies::policy_id.eq(policy_id)),
+    )
+    .set(transfer_policies::payee_address.eq(payee_address))
+    .execute(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+pub fn update_summary(
+    connection: &mut PgConnection,
+    policy: &TransferPolicy,
+) -> Result<usize> {
+    diesel::update(
+        transfer_policies::table
+            .filter(transfer_policies::chain_id.eq(policy.chain_id))
+            .filter(transfer_policies::nft_type.eq(&policy.nft_type)),
+    )
+    .set((
+        transfer_policies::royalty_bp.eq(policy.royalty_bp),
+        transfer_policies::royalty_min_amount.eq(policy.royalty_min_amount),
+        transfer_policies::floor_price.eq(policy.floor_price),
+        transfer_policies::kiosk_lock.eq(policy.kiosk_lock),
+    ))
+    .execute(connection)
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+pub fn query_by_policy_ids(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    policy_ids: Vec<String>,
+) -> Result<Vec<TransferPolicy>> {
+    transfer_policies::table
+        .filter(transfer_policies::chain_id.eq(chain_id))
+        .filter(transfer_policies::policy_id.eq_any(policy_ids))
+        .load::<TransferPolicy>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+pub fn query_rules(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    policy_id: &str,
+) -> Result<Vec<TransferPolicyRule>> {
+    transfer_policy_rules::table
+        .filter(transfer_policy_rules::chain_id.eq(chain_id))
+        .filter(transfer_policy_rules::policy_id.eq(policy_id))
+        .load::<TransferPolicyRule>(connection)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
+/// Royalty basis points and payee of the NFT types that have a royalty
+/// rule.
+pub fn query_royalties(
+    connection: &mut PgConnection,
+    chain_id: i64,
+    nft_types: Vec<String>,
+) -> Result<HashMap<String, (i64, Option<String>)>> {
+    Ok(transfer_policies::table
+        .select((
+            transfer_policies::nft_type,
+            transfer_policies::royalty_bp,
+            transfer_policies::payee_address,
+        ))
+        .filter(transfer_policies::chain_id.eq(chain_id))
+        .filter(transfer_policies::nft_type.eq_any(nft_types))
+        .filter(transfer_policies::royalty_bp.is_not_null())
+        .load::<(String, Option<i64>, Option<String>)>(connection)?
+        .into_iter()
+        .filter_map(|(nft_type, bp, payee)| {
+            bp.map(|bp| (nft_type, (bp, payee)))
+        })
+        .collect())
+}
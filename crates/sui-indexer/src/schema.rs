@@ -1,6 +1,10 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "failed_item_kind"))]
+    pub struct FailedItemKind;
+
     #[derive(diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "activity_type"))]
     pub struct ActivityType;
@@ -13,6 +17,10 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "market_type"))]
     pub struct MarketType;
 
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "marketplace"))]
+    pub struct Marketplace;
+
     #[derive(diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "offer_type"))]
     pub struct OfferType;
@@ -21,6 +29,10 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "order_type"))]
     pub struct OrderType;
 
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "owner_kind"))]
+    pub struct OwnerKind;
+
     #[derive(diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "token_status"))]
     pub struct TokenStatus;
@@ -34,7 +46,7 @@ diesel::table! {
         id -> Int8,
         chain_id -> Int8,
         version -> Int8,
-        tx -> Nullable<Text>,
+        tx -> Text,
         event_account_address -> Text,
         event_creation_number -> Int8,
         event_sequence_number -> Int8,
@@ -47,9 +59,9 @@ diesel::table! {
         transfer_type -> ActivityType,
         from_address -> Nullable<Text>,
         to_address -> Nullable<Text>,
-        token_amount -> Int8,
+        token_amount -> Numeric,
         coin_type -> Nullable<Text>,
-        coin_amount -> Nullable<Int8>,
+        coin_amount -> Nullable<Numeric>,
         transaction_timestamp -> Timestamp,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
@@ -64,7 +76,34 @@ diesel::table! {
 }
 
 diesel::table! {
-    collections (collection_id) {
+    coins (id) {
+        id -> Int4,
+        coin_type -> Text,
+        symbol -> Nullable<Varchar>,
+        name -> Nullable<Varchar>,
+        decimals -> Nullable<Int4>,
+        icon_url -> Nullable<Text>,
+        metadata_id -> Nullable<Varchar>,
+        version -> Int8,
+        created_at -> Int8,
+        updated_at -> Int8,
+    }
+}
+
+diesel::table! {
+    collection_display_versions (chain_id, collection_id, version) {
+        chain_id -> Int8,
+        collection_id -> Varchar,
+        version -> Int8,
+        display_version -> Int8,
+        metadata -> Text,
+        tx -> Nullable<Varchar>,
+        created_at -> Int8,
+    }
+}
+
+diesel::table! {
+    collections (chain_id, collection_id) {
         chain_id -> Int4,
         slug -> Nullable<Varchar>,
         collection_id -> Varchar,
@@ -88,6 +127,41 @@ diesel::table! {
         last_metadata_sync -> Int8,
         created_at -> Int8,
         updated_at -> Int8,
+        minted -> Int8,
+        burned -> Int8,
+        holders -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::FailedItemKind;
+
+    failed_items (id) {
+        id -> Int8,
+        chain_id -> Int8,
+        checkpoint -> Int8,
+        tx_digest -> Varchar,
+        item_kind -> FailedItemKind,
+        item_key -> Varchar,
+        raw_json -> Text,
+        error -> Text,
+        attempts -> Int4,
+        resolved -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    kiosks (chain_id, kiosk_id) {
+        chain_id -> Int8,
+        kiosk_id -> Varchar,
+        owner_cap_id -> Varchar,
+        owner_address -> Varchar,
+        version -> Int8,
+        created_at -> Int8,
+        updated_at -> Int8,
     }
 }
 
@@ -104,12 +178,30 @@ diesel::table! {
         list_time -> Timestamp,
         token_id -> Varchar,
         seller_address -> Varchar,
-        seller_value -> Int8,
+        seller_value -> Numeric,
         expire_time -> Nullable<Timestamp>,
         list_type -> ListType,
         market_type -> MarketType,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        tx_digest -> Varchar,
+        event_seq -> Int8,
+        closed_tx_digest -> Nullable<Varchar>,
+        closed_event_seq -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Marketplace;
+
+    marketplace_packages (chain_id, package_id) {
+        chain_id -> Int8,
+        package_id -> Varchar,
+        marketplace -> Marketplace,
+        upgrade_cap_id -> Nullable<Varchar>,
+        version -> Int8,
+        created_at -> Int8,
     }
 }
 
@@ -124,12 +216,28 @@ diesel::table! {
         offer_id -> Varchar,
         list_id -> Varchar,
         buyer_address -> Varchar,
-        offer_value -> Int8,
+        offer_value -> Numeric,
         offer_type -> OfferType,
-        expire_time -> Timestamp,
+        expire_time -> Nullable<Timestamp>,
         offer_time -> Timestamp,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        tx_digest -> Varchar,
+        event_seq -> Int8,
+        token_id -> Nullable<Varchar>,
+        collection_type -> Nullable<Varchar>,
+        closed_tx_digest -> Nullable<Varchar>,
+        closed_event_seq -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    orderbooks (chain_id, orderbook_id) {
+        chain_id -> Int8,
+        orderbook_id -> Varchar,
+        nft_type -> Text,
+        coin_id -> Int4,
+        created_at -> Int8,
     }
 }
 
@@ -146,19 +254,70 @@ diesel::table! {
         offer_id -> Nullable<Varchar>,
         seller_address -> Varchar,
         buyer_address -> Varchar,
-        value -> Int8,
+        value -> Numeric,
         order_type -> OrderType,
         sell_time -> Timestamp,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        tx_digest -> Varchar,
+        event_seq -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::OwnerKind;
+
+    token_ownership_history (id) {
+        id -> Int8,
+        chain_id -> Int8,
+        token_id -> Varchar,
+        owner_address -> Nullable<Varchar>,
+        owner_kind -> Nullable<OwnerKind>,
+        from_version -> Int8,
+        from_checkpoint -> Int8,
+        from_time -> Int8,
+        to_version -> Nullable<Int8>,
+        to_checkpoint -> Nullable<Int8>,
+        to_time -> Nullable<Int8>,
+        tx_digest -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    token_attributes (chain_id, token_id, trait_type, value) {
+        chain_id -> Int8,
+        token_id -> Varchar,
+        collection_id -> Varchar,
+        trait_type -> Varchar,
+        value -> Varchar,
+        version -> Int8,
+    }
+}
+
+diesel::table! {
+    token_custody (chain_id, token_id) {
+        chain_id -> Int8,
+        token_id -> Varchar,
+        custodian_id -> Nullable<Varchar>,
+        custodian_type -> Nullable<Text>,
+        protocol -> Nullable<Text>,
+        owner_address -> Nullable<Varchar>,
+        version -> Int8,
+        tx_digest -> Varchar,
+        wrapped_at -> Int8,
+        unwrapped_at -> Nullable<Int8>,
+        created_at -> Int8,
+        updated_at -> Int8,
     }
 }
 
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::TokenStatus;
+    use super::sql_types::OwnerKind;
 
-    tokens (token_id) {
+    tokens (chain_id, token_id) {
         chain_id -> Int8,
         token_id -> Varchar,
         collection_id -> Varchar,
@@ -179,15 +338,55 @@ diesel::table! {
         status -> Nullable<TokenStatus>,
         created_at -> Int8,
         updated_at -> Int8,
+        owner_kind -> Nullable<OwnerKind>,
+    }
+}
+
+diesel::table! {
+    transfer_policies (chain_id, nft_type) {
+        chain_id -> Int8,
+        nft_type -> Text,
+        policy_id -> Varchar,
+        rules -> Text,
+        royalty_bp -> Nullable<Int8>,
+        royalty_min_amount -> Nullable<Int8>,
+        floor_price -> Nullable<Int8>,
+        kiosk_lock -> Bool,
+        payee_address -> Nullable<Varchar>,
+        version -> Int8,
+        created_at -> Int8,
+        updated_at -> Int8,
+    }
+}
+
+diesel::table! {
+    transfer_policy_rules (chain_id, policy_id, rule_type) {
+        chain_id -> Int8,
+        policy_id -> Varchar,
+        rule_type -> Text,
+        config -> Text,
+        version -> Int8,
+        updated_at -> Int8,
     }
 }
 
 diesel::allow_tables_to_appear_in_same_query!(
     activities,
     check_point,
+    coins,
+    collection_display_versions,
     collections,
+    failed_items,
+    kiosks,
     lists,
+    marketplace_packages,
     offers,
+    orderbooks,
     orders,
+    token_attributes,
+    token_custody,
+    token_ownership_history,
     tokens,
+    transfer_policies,
+    transfer_policy_rules,
 );
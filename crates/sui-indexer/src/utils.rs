@@ -1,28 +1,33 @@
+use anyhow::{anyhow, Result};
 use serde_json::Value;
 use std::collections::HashMap;
 
-pub fn json_to_kv_map(fields: &Value) -> HashMap<String, String> {
+pub fn json_to_kv_map(fields: &Value) -> Result<HashMap<String, String>> {
     let mut kv_set = HashMap::new();
-    if fields.is_array() {
-        for v in fields.as_array().unwrap().iter() {
-            let name = v["key"].as_str().unwrap().to_string();
-            let value = v["value"].as_str().unwrap().to_string();
-            // .to_string()
-            // .strip_prefix('"')
-            // .unwrap()
-            // .to_string();
+    if let Some(entries) = fields.as_array() {
+        for v in entries.iter() {
+            let name = v["key"]
+                .as_str()
+                .ok_or_else(|| anyhow!("Display entry without a key: {}", v))?
+                .to_string();
+            let value = v["value"]
+                .as_str()
+                .ok_or_else(|| {
+                    anyhow!("Display entry {} is not a string", name)
+                })?
+                .to_string();
             kv_set.insert(name, value);
         }
-    } else if fields.is_object() {
-        fields.as_object().unwrap().iter().for_each(|(k, v)| {
+    } else if let Some(entries) = fields.as_object() {
+        for (k, v) in entries.iter() {
             if k == &"id" {
-                return;
+                continue;
             }
-            kv_set.insert(
-                k.as_str().parse().unwrap(),
-                v.as_str().unwrap().to_string(),
-            );
-        });
+            let value = v.as_str().ok_or_else(|| {
+                anyhow!("Display field {} is not a string", k)
+            })?;
+            kv_set.insert(k.to_string(), value.to_string());
+        }
     }
-    kv_set
+    Ok(kv_set)
 }
@@ -7,13 +7,14 @@ use crate::PgPool;
 use futures::StreamExt;
 use lapin::options::{BasicAckOptions, BasicNackOptions, BasicQosOptions};
 use lapin::types::FieldTable;
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use sui_indexer::config::Network;
 use sui_indexer::indexer::receiver::TOKEN_EXCHANGE;
 use sui_indexer::models::collections::Collection;
 use sui_indexer::models::collections::{
     query_collection, update_collection_metadata,
 };
-//use sui_indexer::models::tokens::count_star;
 use sui_indexer::models::tokens::{update_image_url, Token};
 use tracing::{error, info};
 
@@ -24,6 +25,13 @@ const TOKEN_WRAP: &str = "token.wrap";
 const TOKEN_UNWRAP: &str = "token.unwrap";
 const TOKEN_UNWRAP_THEN_DELETE: &str = "token.unwrap_then_delete";
 
+lazy_static! {
+    static ref NETWORK: Network = std::env::var("SUI_NETWORK")
+        .unwrap_or_else(|_| "mainnet".to_string())
+        .parse()
+        .expect("SUI_NETWORK must be mainnet, testnet, devnet or localnet");
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 struct CollectionObjectId {
     object_id: String,
@@ -73,10 +81,10 @@ pub async fn handle_token_create(
     mut s3: S3Store,
     mut rds: redis::Client,
 ) -> Result<()> {
-    let _ = create_and_bind(&channel, &TOKEN_CREATE).await?;
+    let queue = create_and_bind(&channel, TOKEN_CREATE).await?;
     let mut consumer = channel
         .basic_consume(
-            TOKEN_CREATE,
+            &queue,
             format!("server-side-token-create-worker-{}", i).as_str(),
             lapin::options::BasicConsumeOptions::default(),
             FieldTable::default(),
@@ -143,12 +151,9 @@ pub async fn handle_token_create(
             }
         }
 
-        //query the token numbers
-        // let count =
-        //     count_star(&mut pg, t.collection_id.clone()).unwrap_or_default();
-
         // query the collection
-        if let Ok(mut collection) = query_collection(&mut pg, &t.collection_id)
+        if let Ok(mut collection) =
+            query_collection(&mut pg, t.chain_id, &t.collection_id)
         {
             let mut insert_to_searche_ngine = false;
 
@@ -241,6 +246,7 @@ pub async fn handle_token_create(
 
             if let Err(e) = update_collection_metadata(
                 &mut pg,
+                t.chain_id,
                 &t.collection_id,
                 &collection,
             ) {
@@ -251,7 +257,9 @@ pub async fn handle_token_create(
         }
 
         //info!("count collection_name: {} NFT-number: {}", name, count);
-        if let Err(e) = update_image_url(&mut pg, t.token_id, t.image) {
+        if let Err(e) =
+            update_image_url(&mut pg, t.chain_id, t.token_id, t.image)
+        {
             error!("{}", e);
 
             delivery.nack(nack).await.expect("nack");
@@ -273,11 +281,11 @@ pub async fn handle_token_create(
 }
 
 pub async fn handle_token_update(channel: lapin::Channel) -> Result<()> {
-    let _ = create_and_bind(&channel, &TOKEN_UPDATE).await;
+    let queue = create_and_bind(&channel, TOKEN_UPDATE).await?;
 
     let mut consumer = channel
         .basic_consume(
-            TOKEN_UPDATE,
+            &queue,
             "server-side-token-update-worker",
             lapin::options::BasicConsumeOptions::default(),
             FieldTable::default(),
@@ -302,23 +310,18 @@ pub async fn handle_token_update(channel: lapin::Channel) -> Result<()> {
     Ok(())
 }
 
-pub async fn handle_token_delete(
-    channel: lapin::Channel,
-    pool: PgPool,
-) -> Result<()> {
-    let _ = create_and_bind(&channel, &TOKEN_DELETE).await;
+pub async fn handle_token_delete(channel: lapin::Channel) -> Result<()> {
+    let queue = create_and_bind(&channel, TOKEN_DELETE).await?;
 
     let mut consumer = channel
         .basic_consume(
-            TOKEN_DELETE,
+            &queue,
             "server-side-token-update-worker",
             lapin::options::BasicConsumeOptions::default(),
             FieldTable::default(),
         )
         .await?;
 
-    let mut pg = pool.get()?;
-
     while let Some(delivery) = consumer.next().await {
         let delivery = delivery.expect("error in consumer");
         info!("consumer: {}", TOKEN_DELETE);
@@ -331,23 +334,13 @@ pub async fn handle_token_delete(
             }
         };
 
-        //delete token
-        //set_status_delete(&mut pg, &t.token_id)?;
-
-        let name = t.token_name.clone();
-        let mut display_name: Vec<_> = name.split("#").collect();
-        if display_name.len() > 1 {
-            display_name.remove(display_name.len() - 1);
-            //name = display_name.join(" ").to_string();
-        }
-
-        // query the collection
-        let collection = query_collection(&mut pg, &t.collection_id)?;
-        //query the token numbers
-        //let count = count_star(&mut pg, t.collection_id.clone())?;
-        //info!("count collection_name: {}", name);
-        //collection.supply = count;
-        update_collection_metadata(&mut pg, &t.collection_id, &collection)?;
+        // the indexer already marked the token burned and recounted its
+        // collection.
+        info!(
+            token = t.token_id,
+            collection = t.collection_id,
+            "Token burned"
+        );
 
         delivery.ack(BasicAckOptions::default()).await.expect("ack");
     }
@@ -356,10 +349,10 @@ pub async fn handle_token_delete(
 }
 
 pub async fn handle_token_wrap(channel: lapin::Channel) -> Result<()> {
-    let _ = create_and_bind(&channel, &TOKEN_WRAP).await;
+    let queue = create_and_bind(&channel, TOKEN_WRAP).await?;
     let mut consumer = channel
         .basic_consume(
-            TOKEN_WRAP,
+            &queue,
             "server-side-token-wrap-worker",
             lapin::options::BasicConsumeOptions::default(),
             FieldTable::default(),
@@ -386,10 +379,10 @@ pub async fn handle_token_wrap(channel: lapin::Channel) -> Result<()> {
 }
 
 pub async fn handle_token_unwrap(channel: lapin::Channel) -> Result<()> {
-    let _ = create_and_bind(&channel, &TOKEN_UNWRAP).await?;
+    let queue = create_and_bind(&channel, TOKEN_UNWRAP).await?;
     let mut consumer = channel
         .basic_consume(
-            TOKEN_UNWRAP,
+            &queue,
             "server-side-token-unwrap-worker",
             lapin::options::BasicConsumeOptions::default(),
             FieldTable::default(),
@@ -417,10 +410,10 @@ pub async fn handle_token_unwrap(channel: lapin::Channel) -> Result<()> {
 pub async fn handle_token_unwrap_when_delete(
     channel: lapin::Channel,
 ) -> Result<()> {
-    let _ = create_and_bind(&channel, &TOKEN_UNWRAP_THEN_DELETE).await;
+    let queue = create_and_bind(&channel, TOKEN_UNWRAP_THEN_DELETE).await?;
     let mut consumer = channel
         .basic_consume(
-            TOKEN_UNWRAP_THEN_DELETE,
+            &queue,
             "server-side-token-update-worker",
             lapin::options::BasicConsumeOptions::default(),
             FieldTable::default(),
@@ -444,26 +437,29 @@ pub async fn handle_token_unwrap_when_delete(
     Ok(())
 }
 
+/// Declare the queue for `name` on the configured network and bind it to
+/// the token exchange, returning the queue name.
 pub async fn create_and_bind(
     channel: &lapin::Channel,
     name: &str,
-) -> Result<()> {
+) -> Result<String> {
+    let name = NETWORK.routing_key(name);
     let mut opt = lapin::options::QueueDeclareOptions::default();
     opt.durable = true;
 
     channel
-        .queue_declare(name, opt, FieldTable::default())
+        .queue_declare(&name, opt, FieldTable::default())
         .await?;
 
     channel
         .queue_bind(
-            name,
+            &name,
             TOKEN_EXCHANGE,
-            name,
+            &name,
             lapin::options::QueueBindOptions::default(),
             FieldTable::default(),
         )
         .await?;
 
-    Ok(())
+    Ok(name)
 }
@@ -41,10 +41,7 @@ impl Worker {
 
         let mut workers = vec![];
         workers.push(tokio::spawn(handle_token_update(update_channel)));
-        workers.push(tokio::spawn(handle_token_delete(
-            delete_channel,
-            self.pg.clone(),
-        )));
+        workers.push(tokio::spawn(handle_token_delete(delete_channel)));
         workers
             .push(tokio::spawn(batch_run_create_channel(10, mq, pg, s3, rds)));
         workers.push(tokio::spawn(handle_token_unwrap(unwrap_channel)));